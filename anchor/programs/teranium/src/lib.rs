@@ -1,29 +1,226 @@
-use anchor_lang::prelude::*;
+use anchor_lang::{prelude::*, system_program, Discriminator};
 use anchor_spl::{
-    associated_token::AssociatedToken,
-    token::{self, Mint, Token, TokenAccount, Transfer},
+    associated_token::{get_associated_token_address_with_program_id, AssociatedToken},
+    token_2022::spl_token_2022::{
+        self,
+        extension::{
+            default_account_state::DefaultAccountState, transfer_fee::TransferFeeConfig, transfer_hook,
+            BaseStateWithExtensions, ExtensionType, StateWithExtensions,
+        },
+        state::AccountState,
+    },
+    token_interface::{self, CloseAccount, Mint, SyncNative, TokenAccount, TokenInterface},
 };
-use pyth_sdk_solana::load_price_feed_from_account_info;
+
+mod oracle;
 
 declare_id!("Dx9ZBP9kFYjvZX6sY6bHKgyD3BQtTmnhU6apDpMUAMWV");
 
 const BPS_DENOM: i128 = 10_000;
-const MAX_STALENESS_SECONDS: i64 = 60;
+const MAX_FEE_BPS: u16 = 1_000;
+const MAX_CONF_MULTIPLIER_BPS: u16 = 50_000;
+// Protocol-wide bounds for per-pair oracle staleness limits.
+const DEFAULT_STALENESS_SECONDS: u32 = 60;
+const MAX_STALENESS_SECONDS: u32 = 3_600;
+const MAX_STALENESS_SLOTS: u64 = 9_000;
+
+// Rolling-window volume caps: the window is split into this many time buckets per direction.
+const VOLUME_BUCKETS: usize = 6;
+const MAX_VOLUME_WINDOW_SECONDS: u32 = 7 * 24 * 60 * 60;
+const MAX_CIRCUIT_BREAKER_WINDOW_SECONDS: u32 = 24 * 60 * 60;
+const MAX_FALLBACK_PRICE_TTL_SECONDS: u32 = 3_600;
+const MAX_TRANSFER_HOOK_PROGRAMS: usize = 4;
+
+// Token-2022 mint extensions a vault can hold. Anything else (permanent delegate, non-transferable,
+// confidential transfers, extensions added later) could move or lock vault balances outside the program.
+const ALLOWED_MINT_EXTENSIONS: &[ExtensionType] = &[
+    ExtensionType::TransferFeeConfig,
+    ExtensionType::TransferHook,
+    ExtensionType::DefaultAccountState,
+    ExtensionType::MintCloseAuthority,
+    ExtensionType::InterestBearingConfig,
+    ExtensionType::MetadataPointer,
+    ExtensionType::TokenMetadata,
+    ExtensionType::GroupPointer,
+    ExtensionType::TokenGroup,
+    ExtensionType::GroupMemberPointer,
+    ExtensionType::TokenGroupMember,
+];
+
+// Timelock for risk-parameter changes, and how long an executable change stays valid.
+const DEFAULT_TIMELOCK_SECONDS: u32 = 24 * 60 * 60;
+const MIN_TIMELOCK_SECONDS: u32 = 60 * 60;
+const MAX_TIMELOCK_SECONDS: u32 = 14 * 24 * 60 * 60;
+const CHANGE_GRACE_PERIOD_SECONDS: i64 = 7 * 24 * 60 * 60;
+const PROTOCOL_CONFIG_VERSION: u8 = 1;
 
-const USDC_MINT: Pubkey = pubkey!("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
+const PAUSE_DEPOSITS: u8 = 1 << 0;
+const PAUSE_WITHDRAWALS: u8 = 1 << 1;
+const PAUSE_SWAPS: u8 = 1 << 2;
+const PAUSE_ALL: u8 = PAUSE_DEPOSITS | PAUSE_WITHDRAWALS | PAUSE_SWAPS;
+
+// Account sizes (including discriminator) of the original layouts, before share accounting was introduced:
+// vault = mint, bump, authority_bump, total_deposits; position = owner, vault, deposited.
+// Accounts at or below these sizes are seeded with shares by the migrate instructions.
+const LEGACY_VAULT_ACCOUNT_LEN: usize = 8 + 32 + 1 + 1 + 8;
+const LEGACY_USER_POSITION_LEN: usize = 8 + 32 + 32 + 8;
 
 #[program]
 pub mod teranium {
     use super::*;
 
+    /// Creates the protocol config singleton. Gated on the program upgrade authority
+    /// so the first admin cannot be front-run.
+    pub fn initialize_protocol(ctx: Context<InitializeProtocol>, admin: Pubkey) -> Result<()> {
+        require!(admin != Pubkey::default(), TeraniumError::InvalidAdmin);
+
+        let config = &mut ctx.accounts.protocol_config;
+        config.admin = admin;
+        config.pending_admin = Pubkey::default();
+        config.version = PROTOCOL_CONFIG_VERSION;
+        config.bump = ctx.bumps.protocol_config;
+        config.guardian = Pubkey::default();
+        config.pause_flags = 0;
+        config.treasury = admin;
+        config.timelock_seconds = DEFAULT_TIMELOCK_SECONDS;
+        config.next_change_id = 0;
+
+        emit!(ProtocolInitialized {
+            protocol_config: config.key(),
+            admin,
+            version: config.version,
+        });
+
+        Ok(())
+    }
+
+    /// First step of an admin transfer. Proposing `Pubkey::default()` cancels a pending transfer.
+    pub fn propose_admin(ctx: Context<ProposeAdmin>, new_admin: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.protocol_config;
+        config.pending_admin = new_admin;
+
+        emit!(AdminTransferProposed {
+            protocol_config: config.key(),
+            admin: config.admin,
+            pending_admin: new_admin,
+        });
+
+        Ok(())
+    }
+
+    /// Second step of an admin transfer; must be signed by the pending admin.
+    pub fn accept_admin(ctx: Context<AcceptAdmin>) -> Result<()> {
+        let config = &mut ctx.accounts.protocol_config;
+        require!(config.pending_admin != Pubkey::default(), TeraniumError::NoPendingAdmin);
+        require_keys_eq!(config.pending_admin, ctx.accounts.pending_admin.key(), TeraniumError::Unauthorized);
+
+        let previous_admin = config.admin;
+        config.admin = config.pending_admin;
+        config.pending_admin = Pubkey::default();
+
+        emit!(AdminTransferAccepted {
+            protocol_config: config.key(),
+            previous_admin,
+            admin: config.admin,
+        });
+
+        Ok(())
+    }
+
+    /// Sets the guardian that may pause (but not unpause) the protocol and vaults.
+    /// `Pubkey::default()` removes the guardian.
+    pub fn set_guardian(ctx: Context<SetGuardian>, guardian: Pubkey) -> Result<()> {
+        let config = &mut ctx.accounts.protocol_config;
+        let previous_guardian = config.guardian;
+        config.guardian = guardian;
+
+        emit!(GuardianUpdated {
+            protocol_config: config.key(),
+            previous_guardian,
+            guardian,
+        });
+
+        Ok(())
+    }
+
+    /// Replaces the pause flags of a vault, or of the whole protocol when no vault is passed.
+    ///
+    /// - Admin or guardian may set flags.
+    /// - Only the admin may clear a flag.
+    pub fn set_pause(ctx: Context<SetPause>, pause_flags: u8) -> Result<()> {
+        require!(pause_flags & !PAUSE_ALL == 0, TeraniumError::InvalidPauseFlags);
+
+        let authority = ctx.accounts.authority.key();
+        let config = &mut ctx.accounts.protocol_config;
+        let is_admin = authority == config.admin;
+        let is_guardian = config.guardian != Pubkey::default() && authority == config.guardian;
+        require!(is_admin || is_guardian, TeraniumError::Unauthorized);
+
+        let (target, previous_flags) = match ctx.accounts.vault.as_mut() {
+            Some(vault) => {
+                let previous = vault.pause_flags;
+                vault.pause_flags = pause_flags;
+                (vault.key(), previous)
+            }
+            None => {
+                let previous = config.pause_flags;
+                config.pause_flags = pause_flags;
+                (config.key(), previous)
+            }
+        };
+
+        // Clearing any flag is an unpause and is reserved for the admin.
+        require!(previous_flags & !pause_flags == 0 || is_admin, TeraniumError::Unauthorized);
+
+        emit!(PauseUpdated {
+            authority,
+            target,
+            previous_flags,
+            pause_flags,
+        });
+
+        Ok(())
+    }
+
+    /// Sets the wallet whose token accounts receive collected fees.
+    pub fn set_treasury(ctx: Context<SetTreasury>, treasury: Pubkey) -> Result<()> {
+        require!(treasury != Pubkey::default(), TeraniumError::InvalidTreasury);
+
+        let config = &mut ctx.accounts.protocol_config;
+        let previous_treasury = config.treasury;
+        config.treasury = treasury;
+
+        emit!(TreasuryUpdated {
+            protocol_config: config.key(),
+            previous_treasury,
+            treasury,
+        });
+
+        Ok(())
+    }
+
+    /// Creates the vault for a mint (permissionless).
+    ///
+    /// - Token-2022 mints must have no freeze authority and only extensions in `ALLOWED_MINT_EXTENSIONS`,
+    ///   with `DefaultAccountState` (if present) not `Frozen`; otherwise fails with `UnsupportedMint`.
     pub fn initialize_vault(ctx: Context<InitializeVault>, mint: Pubkey) -> Result<()> {
         require_keys_eq!(ctx.accounts.mint.key(), mint, TeraniumError::MintMismatch);
+        check_mint_extensions(&ctx.accounts.mint)?;
 
         let vault = &mut ctx.accounts.vault;
         vault.mint = mint;
         vault.bump = ctx.bumps.vault;
         vault.authority_bump = ctx.bumps.vault_authority;
         vault.total_deposits = 0;
+        vault.pause_flags = 0;
+        vault.accrued_fees = 0;
+        vault.total_shares = 0;
+        vault.max_total_deposits = 0;
+        vault.max_deposit_per_user = 0;
+        vault.decommissioned = false;
+        vault.pair_count = 0;
+        vault.pending_changes = 0;
+        vault.total_principal = 0;
 
         emit!(VaultInitialized {
             vault: vault.key(),
@@ -35,390 +232,2840 @@ pub mod teranium {
         Ok(())
     }
 
-    pub fn deposit(ctx: Context<Deposit>, amount: u64) -> Result<()> {
-        require!(amount > 0, TeraniumError::InvalidAmount);
+    pub fn deposit<'info>(ctx: Context<'_, '_, '_, 'info, Deposit<'info>>, amount: u64) -> Result<()> {
+        require_keys_eq!(ctx.accounts.user_token_account.mint, ctx.accounts.vault.mint, TeraniumError::MintMismatch);
+        require_keys_eq!(ctx.accounts.vault_token_account.mint, ctx.accounts.vault.mint, TeraniumError::MintMismatch);
+        prepare_deposit(
+            &ctx.accounts.protocol_config,
+            &ctx.accounts.vault,
+            &mut ctx.accounts.user_position,
+            ctx.accounts.owner.key(),
+            amount,
+        )?;
+        check_transfer_hook(&ctx.accounts.vault, &ctx.accounts.mint)?;
 
-        let vault = &mut ctx.accounts.vault;
-        require_keys_eq!(ctx.accounts.user_token_account.mint, vault.mint, TeraniumError::MintMismatch);
-        require_keys_eq!(ctx.accounts.vault_token_account.mint, vault.mint, TeraniumError::MintMismatch);
+        // Credit what the vault actually received; Token-2022 transfer fees are withheld from `amount`.
+        let balance_before = ctx.accounts.vault_token_account.amount;
+        transfer_checked(
+            &ctx.accounts.token_program,
+            ctx.accounts.user_token_account.to_account_info(),
+            &ctx.accounts.mint,
+            ctx.accounts.vault_token_account.to_account_info(),
+            ctx.accounts.owner.to_account_info(),
+            ctx.remaining_accounts,
+            amount,
+            &[],
+        )?;
+        ctx.accounts.vault_token_account.reload()?;
+        let received = ctx
+            .accounts
+            .vault_token_account
+            .amount
+            .checked_sub(balance_before)
+            .ok_or(TeraniumError::MathOverflow)?;
 
-        if ctx.accounts.user_position.owner == Pubkey::default() {
-            ctx.accounts.user_position.owner = ctx.accounts.owner.key();
-            ctx.accounts.user_position.vault = vault.key();
-            ctx.accounts.user_position.deposited = 0;
-        }
+        credit_deposit(
+            &mut ctx.accounts.vault,
+            &mut ctx.accounts.user_position,
+            ctx.accounts.owner.key(),
+            received,
+        )
+    }
 
-        require_keys_eq!(ctx.accounts.user_position.owner, ctx.accounts.owner.key(), TeraniumError::Unauthorized);
-        require_keys_eq!(ctx.accounts.user_position.vault, vault.key(), TeraniumError::InvalidUserPosition);
+    /// Deposits `amount` lamports into a wrapped SOL vault.
+    ///
+    /// - Moves the lamports straight into the vault's native token account and syncs its balance,
+    ///   so the user needs no wSOL account.
+    /// - Same pause, cap and share accounting as `deposit`; fails with `NotNativeMint` for other vaults.
+    pub fn deposit_sol(ctx: Context<DepositSol>, amount: u64) -> Result<()> {
+        require_keys_eq!(ctx.accounts.vault_token_account.mint, ctx.accounts.vault.mint, TeraniumError::MintMismatch);
+        require!(ctx.accounts.vault_token_account.is_native(), TeraniumError::NotNativeMint);
+        prepare_deposit(
+            &ctx.accounts.protocol_config,
+            &ctx.accounts.vault,
+            &mut ctx.accounts.user_position,
+            ctx.accounts.owner.key(),
+            amount,
+        )?;
 
-        token::transfer(
+        system_program::transfer(
             CpiContext::new(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.user_token_account.to_account_info(),
+                ctx.accounts.system_program.to_account_info(),
+                system_program::Transfer {
+                    from: ctx.accounts.owner.to_account_info(),
                     to: ctx.accounts.vault_token_account.to_account_info(),
-                    authority: ctx.accounts.owner.to_account_info(),
                 },
             ),
             amount,
         )?;
+        token_interface::sync_native(CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            SyncNative {
+                account: ctx.accounts.vault_token_account.to_account_info(),
+            },
+        ))?;
 
-        ctx.accounts.user_position.deposited = ctx
-            .accounts
-            .user_position
-            .deposited
-            .checked_add(amount)
-            .ok_or(TeraniumError::MathOverflow)?;
-
-        vault.total_deposits = vault
-            .total_deposits
-            .checked_add(amount)
-            .ok_or(TeraniumError::MathOverflow)?;
+        // Wrapping has no transfer fee; lamports sent to the account by others stay surplus.
+        credit_deposit(
+            &mut ctx.accounts.vault,
+            &mut ctx.accounts.user_position,
+            ctx.accounts.owner.key(),
+            amount,
+        )
+    }
 
-        emit!(Deposited {
-            owner: ctx.accounts.owner.key(),
-            vault: vault.key(),
+    pub fn withdraw<'info>(ctx: Context<'_, '_, '_, 'info, Withdraw<'info>>, amount: u64) -> Result<()> {
+        require_keys_eq!(ctx.accounts.user_token_account.mint, ctx.accounts.vault.mint, TeraniumError::MintMismatch);
+        require_keys_eq!(ctx.accounts.vault_token_account.mint, ctx.accounts.vault.mint, TeraniumError::MintMismatch);
+        let (shares, principal) = prepare_withdraw(
+            &ctx.accounts.protocol_config,
+            &ctx.accounts.vault,
+            &ctx.accounts.user_position,
+            ctx.accounts.owner.key(),
             amount,
-            deposited_after: ctx.accounts.user_position.deposited,
-            total_deposits_after: vault.total_deposits,
-        });
+        )?;
 
-        Ok(())
-    }
+        let vault_key = ctx.accounts.vault.key();
+        let authority_seeds: &[&[u8]] = &[
+            b"vault_authority",
+            vault_key.as_ref(),
+            &[ctx.accounts.vault.authority_bump],
+        ];
+
+        transfer_checked(
+            &ctx.accounts.token_program,
+            ctx.accounts.vault_token_account.to_account_info(),
+            &ctx.accounts.mint,
+            ctx.accounts.user_token_account.to_account_info(),
+            ctx.accounts.vault_authority.to_account_info(),
+            ctx.remaining_accounts,
+            amount,
+            &[authority_seeds],
+        )?;
 
-    pub fn withdraw(ctx: Context<Withdraw>, amount: u64) -> Result<()> {
-        require!(amount > 0, TeraniumError::InvalidAmount);
+        debit_withdraw(
+            &mut ctx.accounts.vault,
+            &mut ctx.accounts.user_position,
+            ctx.accounts.owner.key(),
+            amount,
+            shares,
+            principal,
+        )?;
 
-        let vault = &mut ctx.accounts.vault;
-        require_keys_eq!(ctx.accounts.user_token_account.mint, vault.mint, TeraniumError::MintMismatch);
-        require_keys_eq!(ctx.accounts.vault_token_account.mint, vault.mint, TeraniumError::MintMismatch);
+        if ctx.accounts.user_position.shares == 0 {
+            close_position_account(&ctx.accounts.user_position, &ctx.accounts.owner)?;
+        }
 
-        require_keys_eq!(ctx.accounts.user_position.owner, ctx.accounts.owner.key(), TeraniumError::Unauthorized);
-        require_keys_eq!(ctx.accounts.user_position.vault, vault.key(), TeraniumError::InvalidUserPosition);
+        Ok(())
+    }
 
-        require!(amount <= ctx.accounts.user_position.deposited, TeraniumError::InsufficientDepositedBalance);
+    /// Withdraws `amount` from a wrapped SOL vault as lamports.
+    ///
+    /// - Moves the tokens into a temporary native account (PDA `["unwrap", vault, owner]`, rent paid by the owner)
+    ///   and closes it to the owner in the same instruction, so the owner receives `amount` lamports plus the rent back.
+    /// - Same pause and share accounting as `withdraw`; fails with `NotNativeMint` for other vaults.
+    pub fn withdraw_sol(ctx: Context<WithdrawSol>, amount: u64) -> Result<()> {
+        require_keys_eq!(ctx.accounts.vault_token_account.mint, ctx.accounts.vault.mint, TeraniumError::MintMismatch);
+        require!(ctx.accounts.vault_token_account.is_native(), TeraniumError::NotNativeMint);
+        let (shares, principal) = prepare_withdraw(
+            &ctx.accounts.protocol_config,
+            &ctx.accounts.vault,
+            &ctx.accounts.user_position,
+            ctx.accounts.owner.key(),
+            amount,
+        )?;
 
+        let vault_key = ctx.accounts.vault.key();
         let authority_seeds: &[&[u8]] = &[
             b"vault_authority",
-            vault.key().as_ref(),
-            &[vault.authority_bump],
+            vault_key.as_ref(),
+            &[ctx.accounts.vault.authority_bump],
         ];
 
-        token::transfer(
-            CpiContext::new_with_signer(
-                ctx.accounts.token_program.to_account_info(),
-                Transfer {
-                    from: ctx.accounts.vault_token_account.to_account_info(),
-                    to: ctx.accounts.user_token_account.to_account_info(),
-                    authority: ctx.accounts.vault_authority.to_account_info(),
-                },
-                &[authority_seeds],
-            ),
+        transfer_checked(
+            &ctx.accounts.token_program,
+            ctx.accounts.vault_token_account.to_account_info(),
+            &ctx.accounts.mint,
+            ctx.accounts.unwrap_token_account.to_account_info(),
+            ctx.accounts.vault_authority.to_account_info(),
+            &[],
             amount,
+            &[authority_seeds],
         )?;
+        token_interface::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.unwrap_token_account.to_account_info(),
+                destination: ctx.accounts.owner.to_account_info(),
+                authority: ctx.accounts.vault_authority.to_account_info(),
+            },
+            &[authority_seeds],
+        ))?;
 
-        ctx.accounts.user_position.deposited = ctx
-            .accounts
-            .user_position
-            .deposited
-            .checked_sub(amount)
-            .ok_or(TeraniumError::MathOverflow)?;
-
-        vault.total_deposits = vault
-            .total_deposits
-            .checked_sub(amount)
-            .ok_or(TeraniumError::MathOverflow)?;
-
-        emit!(Withdrawn {
-            owner: ctx.accounts.owner.key(),
-            vault: vault.key(),
+        debit_withdraw(
+            &mut ctx.accounts.vault,
+            &mut ctx.accounts.user_position,
+            ctx.accounts.owner.key(),
             amount,
-            deposited_after: ctx.accounts.user_position.deposited,
-            total_deposits_after: vault.total_deposits,
-        });
+            shares,
+            principal,
+        )?;
+
+        if ctx.accounts.user_position.shares == 0 {
+            close_position_account(&ctx.accounts.user_position, &ctx.accounts.owner)?;
+        }
 
         Ok(())
     }
 
-    /// Oracle-priced swap between a base mint vault and the USDC vault.
+    /// Closes an empty user position and returns its rent to the owner.
     ///
-    /// - Uses a Pyth price feed (legacy price account) for base mint USD price.
-    /// - Uses oracle confidence interval as a deterministic slippage bound.
-    /// - Enforces staleness using publish_time.
-    /// - Ensures vaults remain solvent against `total_deposits` after swap.
-    pub fn oracle_swap(ctx: Context<OracleSwap>, amount: u64, max_slippage_bps: u16) -> Result<()> {
-        require!(amount > 0, TeraniumError::InvalidAmount);
-        require!(max_slippage_bps as i128 <= BPS_DENOM, TeraniumError::InvalidSlippageBps);
+    /// - Shares worth less than one token unit, which no withdrawal can burn, are forfeited to the vault
+    ///   (`vault` required); fails with `PositionNotEmpty` while the shares are worth more.
+    /// - `withdraw` and `withdraw_sol` already close the position when they burn its last share;
+    ///   this covers positions left empty or with dust otherwise.
+    pub fn close_position(ctx: Context<ClosePosition>) -> Result<()> {
+        if ctx.accounts.user_position.shares > 0 {
+            let vault = ctx.accounts.vault.as_mut().ok_or(TeraniumError::PositionNotEmpty)?;
+            forfeit_dust_shares(vault, &mut ctx.accounts.user_position)?;
+        }
+        close_position_account(&ctx.accounts.user_position, &ctx.accounts.owner)
+    }
+
+    /// Upgrades a vault account to the current layout (permissionless).
+    ///
+    /// - Reallocates the account, zero-filling new fields; the payer covers extra rent.
+    /// - Vaults that predate share accounting get `total_shares` and `total_principal` = recorded principal and
+    ///   `total_deposits` synced to the vault token balance, so surplus above principal goes to existing LPs.
+    pub fn migrate_vault(ctx: Context<MigrateVault>) -> Result<()> {
+        let info = ctx.accounts.vault.to_account_info();
+        let old_len = info.data_len();
+        require!(old_len < 8 + VaultAccount::INIT_SPACE, TeraniumError::AlreadyMigrated);
+        require!(
+            info.try_borrow_data()?.starts_with(&VaultAccount::DISCRIMINATOR),
+            TeraniumError::InvalidMigrationAccount
+        );
 
-        require_keys_eq!(ctx.accounts.usdc_mint.key(), USDC_MINT, TeraniumError::InvalidUsdcMint);
-        require_keys_eq!(ctx.accounts.usdc_vault.mint, ctx.accounts.usdc_mint.key(), TeraniumError::MintMismatch);
-        require_keys_eq!(ctx.accounts.base_vault.mint, ctx.accounts.base_mint.key(), TeraniumError::MintMismatch);
-        require!(ctx.accounts.base_vault.mint != ctx.accounts.usdc_mint.key(), TeraniumError::InvalidSwapPair);
+        grow_account(&info, &ctx.accounts.payer, &ctx.accounts.system_program, 8 + VaultAccount::INIT_SPACE)?;
 
-        require_keys_eq!(ctx.accounts.base_vault_token_account.mint, ctx.accounts.base_vault.mint, TeraniumError::MintMismatch);
-        require_keys_eq!(ctx.accounts.usdc_vault_token_account.mint, ctx.accounts.usdc_vault.mint, TeraniumError::MintMismatch);
+        let mut vault = VaultAccount::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+        if old_len <= LEGACY_VAULT_ACCOUNT_LEN {
+            let token_account = &ctx.accounts.vault_token_account;
+            let vault_authority = Pubkey::create_program_address(
+                &[b"vault_authority", info.key.as_ref(), &[vault.authority_bump]],
+                &crate::ID,
+            )
+            .map_err(|_| TeraniumError::InvalidMigrationAccount)?;
+            require_keys_eq!(
+                token_account.key(),
+                get_associated_token_address_with_program_id(
+                    &vault_authority,
+                    &vault.mint,
+                    token_account.to_account_info().owner
+                ),
+                TeraniumError::InvalidMigrationAccount
+            );
 
-        // Determine direction from token account mints.
-        let from_mint = ctx.accounts.user_from_token_account.mint;
-        let to_mint = ctx.accounts.user_to_token_account.mint;
+            vault.total_shares = vault.total_deposits;
+            vault.total_principal = vault.total_deposits;
+            vault.total_deposits = token_account.amount;
+        }
+        vault.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
 
-        let base_mint = ctx.accounts.base_mint.key();
-        let usdc_mint = ctx.accounts.usdc_mint.key();
+        emit!(VaultMigrated {
+            vault: info.key(),
+            total_deposits: vault.total_deposits,
+            total_shares: vault.total_shares,
+        });
 
-        let base_decimals = ctx.accounts.base_mint.decimals as u32;
-        let usdc_decimals = ctx.accounts.usdc_mint.decimals as u32;
+        Ok(())
+    }
 
+    /// Upgrades a user position to the current layout (permissionless).
+    ///
+    /// - The vault must already be migrated.
+    /// - Positions that predate share accounting get `shares = deposited`, matching the vault's 1:1 seed.
+    pub fn migrate_position(ctx: Context<MigratePosition>) -> Result<()> {
+        let info = ctx.accounts.user_position.to_account_info();
+        let old_len = info.data_len();
+        require!(old_len < 8 + UserPosition::INIT_SPACE, TeraniumError::AlreadyMigrated);
         require!(
-            (from_mint == base_mint && to_mint == usdc_mint) || (from_mint == usdc_mint && to_mint == base_mint),
-            TeraniumError::InvalidSwapPair
+            info.try_borrow_data()?.starts_with(&UserPosition::DISCRIMINATOR),
+            TeraniumError::InvalidMigrationAccount
         );
 
-        // Load oracle (Pyth legacy price feed).
-        let price_feed = load_price_feed_from_account_info(&ctx.accounts.pyth_price_account)?;
-        let price = price_feed
-            .get_current_price()
-            .ok_or(TeraniumError::OracleNoPrice)?;
+        grow_account(&info, &ctx.accounts.payer, &ctx.accounts.system_program, 8 + UserPosition::INIT_SPACE)?;
+
+        let mut position = UserPosition::try_deserialize(&mut &info.try_borrow_data()?[..])?;
+        require_keys_eq!(position.vault, ctx.accounts.vault.key(), TeraniumError::InvalidUserPosition);
+        if old_len <= LEGACY_USER_POSITION_LEN {
+            position.shares = position.deposited;
+        }
+        position.try_serialize(&mut &mut info.try_borrow_mut_data()?[..])?;
+
+        emit!(PositionMigrated {
+            user_position: info.key(),
+            owner: position.owner,
+            vault: position.vault,
+            shares: position.shares,
+        });
+
+        Ok(())
+    }
+
+    /// Registers the oracle feed that prices a base vault against the quote vault.
+    ///
+    /// - Gated on the protocol admin.
+    /// - The quote vault's mint becomes the pair's quote asset (e.g. USDC, USDT, PYUSD).
+    /// - Binds the pair to a single legacy Pyth price account, owned by the Pyth oracle program;
+    ///   a `PairOracle` change can switch it to a pull feed.
+    /// - Starts with the circuit breaker tripped: the pair cannot swap against the shared vaults until its
+    ///   risk parameters have gone through the timelock and the admin or guardian calls `reset_circuit_breaker`.
+    pub fn initialize_pair(ctx: Context<InitializePair>) -> Result<()> {
+        require!(ctx.accounts.base_vault.mint != ctx.accounts.quote_vault.mint, TeraniumError::InvalidSwapPair);
+
+        let binding = oracle::bind(
+            OracleKind::PythLegacy as u8,
+            [0; 32],
+            0,
+            &ctx.accounts.pyth_price_account,
+        )?;
+
+        let pair = &mut ctx.accounts.pair_config;
+        pair.base_vault = ctx.accounts.base_vault.key();
+        pair.quote_vault = ctx.accounts.quote_vault.key();
+        pair.quote_mint = ctx.accounts.quote_vault.mint;
+        pair.bump = ctx.bumps.pair_config;
+        pair.fee_bps = 0;
+        pair.pricing_mode = PricingMode::Mid as u8;
+        pair.conf_multiplier_bps = 0;
+        pair.set_primary_oracle(&binding);
+        pair.secondary_oracle = OracleBinding::default();
+        pair.max_oracle_deviation_bps = 0;
+        pair.dual_oracle_pricing = DualOraclePricing::Conservative as u8;
+        pair.max_staleness_seconds = DEFAULT_STALENESS_SECONDS;
+        pair.max_staleness_slots = 0;
+        pair.max_ema_deviation_bps = 0;
+        pair.max_trade_notional = 0;
+        pair.max_window_notional = 0;
+        pair.volume_window_seconds = 0;
+        pair.volume_bucket = 0;
+        pair.base_to_quote_volume = [0; VOLUME_BUCKETS];
+        pair.quote_to_base_volume = [0; VOLUME_BUCKETS];
+        pair.last_price = 0;
+        pair.last_price_expo = 0;
+        pair.last_price_timestamp = 0;
+        pair.max_price_jump_bps = 0;
+        pair.circuit_breaker_window_seconds = 0;
+        pair.circuit_breaker_tripped = true;
+        pair.max_fallback_deviation_bps = 0;
+        pair.max_fallback_trade_notional = 0;
+        pair.fallback_price = 0;
+        pair.fallback_conf = 0;
+        pair.fallback_expo = 0;
+        pair.fallback_expires_at = 0;
+        pair.pending_changes = 0;
+
+        for vault in [&mut ctx.accounts.base_vault, &mut ctx.accounts.quote_vault] {
+            vault.pair_count = vault.pair_count.checked_add(1).ok_or(TeraniumError::MathOverflow)?;
+        }
+
+        emit!(PairInitialized {
+            pair_config: pair.key(),
+            base_vault: pair.base_vault,
+            quote_vault: pair.quote_vault,
+            oracle: pair.oracle,
+            oracle_owner: pair.oracle_owner,
+            quote_mint: pair.quote_mint,
+        });
+
+        Ok(())
+    }
+
+    /// Queues a risk-parameter change (fees, pricing, oracle bindings, staleness, caps, the timelock itself)
+    /// in a `PendingChange` PDA; the admin can execute it once `protocol_config.timelock_seconds` have passed.
+    ///
+    /// - Arguments are validated now and again on execution.
+    /// - Pair changes take `pair_config`, vault changes take `vault`; oracle changes also take the price
+    ///   account, which must load under the new binding.
+    pub fn propose_change(ctx: Context<ProposeChange>, change: ParamChange) -> Result<()> {
+        change.validate()?;
+        let target = change_target(
+            &change,
+            &ctx.accounts.protocol_config,
+            &ctx.accounts.pair_config,
+            &ctx.accounts.vault,
+        )?;
+        if let Some(binding) = change.oracle_binding() {
+            bind_change_oracle(binding, ctx.accounts.price_account.as_deref())?;
+        }
+        track_pending_change(&change, &mut ctx.accounts.pair_config, &mut ctx.accounts.vault, true)?;
 
-        // Staleness enforcement.
         let now = Clock::get()?.unix_timestamp;
-        let age = now
-            .checked_sub(price.publish_time)
-            .ok_or(TeraniumError::OracleStale)?;
-        require!(age <= MAX_STALENESS_SECONDS, TeraniumError::OracleStale);
+        let config = &mut ctx.accounts.protocol_config;
+        let id = config.next_change_id;
+        let eta = now
+            .checked_add(config.timelock_seconds as i64)
+            .ok_or(TeraniumError::MathOverflow)?;
+        config.next_change_id = id.checked_add(1).ok_or(TeraniumError::MathOverflow)?;
 
-        // Confidence-based slippage bound (conf/|price| <= max_slippage_bps).
-        let px_i128: i128 = price.price as i128;
-        require!(px_i128 != 0, TeraniumError::OracleInvalidPrice);
-        require!(px_i128 > 0, TeraniumError::OracleInvalidPrice);
+        let pending = &mut ctx.accounts.pending_change;
+        pending.id = id;
+        pending.bump = ctx.bumps.pending_change;
+        pending.proposer = ctx.accounts.admin.key();
+        pending.target = target;
+        pending.proposed_at = now;
+        pending.eta = eta;
+        pending.change = change.clone();
 
-        let abs_px: i128 = px_i128;
-        let conf_i128: i128 = price.conf as i128;
-        let max_bps: i128 = max_slippage_bps as i128;
+        emit!(ChangeProposed {
+            pending_change: pending.key(),
+            id,
+            target,
+            eta,
+            change,
+        });
 
-        require!(conf_i128 >= 0, TeraniumError::OracleInvalidConfidence);
+        Ok(())
+    }
 
-        // conf * 10_000 <= price * max_slippage_bps
+    /// Applies a queued change after its delay (admin only) and closes the `PendingChange` to the proposer.
+    ///
+    /// - Fails with `TimelockNotElapsed` before `eta` and `ChangeExpired` more than 7 days after it.
+    /// - Emits the same update event the change's target used to emit (e.g. `PairFeeUpdated`).
+    pub fn execute_change(ctx: Context<ExecuteChange>) -> Result<()> {
+        let pending = &ctx.accounts.pending_change;
+        let now = Clock::get()?.unix_timestamp;
+        require!(now >= pending.eta, TeraniumError::TimelockNotElapsed);
         require!(
-            conf_i128
-                .checked_mul(BPS_DENOM)
-                .ok_or(TeraniumError::MathOverflow)?
-                <= abs_px
-                    .checked_mul(max_bps)
-                    .ok_or(TeraniumError::MathOverflow)?,
-            TeraniumError::OracleSlippageExceeded
+            now <= pending
+                .eta
+                .checked_add(CHANGE_GRACE_PERIOD_SECONDS)
+                .ok_or(TeraniumError::MathOverflow)?,
+            TeraniumError::ChangeExpired
         );
 
-        let amount_u128: u128 = amount as u128;
-        let expo: i32 = price.expo;
-
-        let (amount_out, direction) = if from_mint == base_mint {
-            // base -> usdc
-            let usdc_out = base_to_usdc(amount_u128, abs_px as u128, expo, base_decimals, usdc_decimals)?;
-            require!(usdc_out > 0, TeraniumError::SwapZeroOut);
-
-            // Ensure USDC vault remains solvent against deposits after paying out.
-            let post = ctx
-                .accounts
-                .usdc_vault_token_account
-                .amount
-                .checked_sub(usdc_out as u64)
-                .ok_or(TeraniumError::InsufficientVaultLiquidity)?;
-            require!(post >= ctx.accounts.usdc_vault.total_deposits, TeraniumError::InsufficientVaultLiquidity);
-
-            // User pays base into base vault.
-            token::transfer(
-                CpiContext::new(
-                    ctx.accounts.token_program.to_account_info(),
-                    Transfer {
-                        from: ctx.accounts.user_from_token_account.to_account_info(),
-                        to: ctx.accounts.base_vault_token_account.to_account_info(),
-                        authority: ctx.accounts.user.to_account_info(),
-                    },
-                ),
-                amount,
-            )?;
+        let change = pending.change.clone();
+        change.validate()?;
+        let target = change_target(
+            &change,
+            &ctx.accounts.protocol_config,
+            &ctx.accounts.pair_config,
+            &ctx.accounts.vault,
+        )?;
+        require_keys_eq!(target, pending.target, TeraniumError::ChangeTargetMismatch);
+        track_pending_change(&change, &mut ctx.accounts.pair_config, &mut ctx.accounts.vault, false)?;
 
-            // Vault pays USDC to user.
-            let usdc_auth_seeds: &[&[u8]] = &[
-                b"vault_authority",
-                ctx.accounts.usdc_vault.key().as_ref(),
-                &[ctx.accounts.usdc_vault.authority_bump],
-            ];
-
-            token::transfer(
-                CpiContext::new_with_signer(
-                    ctx.accounts.token_program.to_account_info(),
-                    Transfer {
-                        from: ctx.accounts.usdc_vault_token_account.to_account_info(),
-                        to: ctx.accounts.user_to_token_account.to_account_info(),
-                        authority: ctx.accounts.usdc_vault_authority.to_account_info(),
-                    },
-                    &[usdc_auth_seeds],
-                ),
-                usdc_out as u64,
-            )?;
+        match change {
+            ParamChange::Timelock { timelock_seconds } => {
+                let config = &mut ctx.accounts.protocol_config;
+                let previous_timelock_seconds = config.timelock_seconds;
+                config.timelock_seconds = timelock_seconds;
 
-            (usdc_out as u64, SwapDirection::BaseToUsdc)
-        } else {
-            // usdc -> base
-            let base_out = usdc_to_base(amount_u128, abs_px as u128, expo, base_decimals, usdc_decimals)?;
-            require!(base_out > 0, TeraniumError::SwapZeroOut);
-
-            // Ensure base vault remains solvent against deposits after paying out.
-            let post = ctx
-                .accounts
-                .base_vault_token_account
-                .amount
-                .checked_sub(base_out as u64)
-                .ok_or(TeraniumError::InsufficientVaultLiquidity)?;
-            require!(post >= ctx.accounts.base_vault.total_deposits, TeraniumError::InsufficientVaultLiquidity);
-
-            // User pays USDC into USDC vault.
-            token::transfer(
-                CpiContext::new(
-                    ctx.accounts.token_program.to_account_info(),
-                    Transfer {
-                        from: ctx.accounts.user_from_token_account.to_account_info(),
-                        to: ctx.accounts.usdc_vault_token_account.to_account_info(),
-                        authority: ctx.accounts.user.to_account_info(),
-                    },
-                ),
-                amount,
-            )?;
+                emit!(TimelockUpdated {
+                    protocol_config: config.key(),
+                    previous_timelock_seconds,
+                    timelock_seconds,
+                });
+            }
+            ParamChange::VaultDepositCaps {
+                max_total_deposits,
+                max_deposit_per_user,
+            } => {
+                let vault = ctx.accounts.vault.as_mut().ok_or(TeraniumError::ChangeTargetMismatch)?;
+                vault.max_total_deposits = max_total_deposits;
+                vault.max_deposit_per_user = max_deposit_per_user;
 
-            // Vault pays base to user.
-            let base_auth_seeds: &[&[u8]] = &[
-                b"vault_authority",
-                ctx.accounts.base_vault.key().as_ref(),
-                &[ctx.accounts.base_vault.authority_bump],
-            ];
-
-            token::transfer(
-                CpiContext::new_with_signer(
-                    ctx.accounts.token_program.to_account_info(),
-                    Transfer {
-                        from: ctx.accounts.base_vault_token_account.to_account_info(),
-                        to: ctx.accounts.user_to_token_account.to_account_info(),
-                        authority: ctx.accounts.base_vault_authority.to_account_info(),
-                    },
-                    &[base_auth_seeds],
-                ),
-                base_out as u64,
-            )?;
+                emit!(VaultDepositCapsUpdated {
+                    vault: vault.key(),
+                    max_total_deposits,
+                    max_deposit_per_user,
+                });
+            }
+            ParamChange::VaultTransferHooks { programs } => {
+                let vault = ctx.accounts.vault.as_mut().ok_or(TeraniumError::ChangeTargetMismatch)?;
+                vault.transfer_hook_programs = programs;
 
-            (base_out as u64, SwapDirection::UsdcToBase)
-        };
+                emit!(VaultTransferHooksUpdated {
+                    vault: vault.key(),
+                    programs,
+                });
+            }
+            _ => {
+                let pair = ctx.accounts.pair_config.as_mut().ok_or(TeraniumError::ChangeTargetMismatch)?;
+                apply_pair_change(pair, &change, ctx.accounts.price_account.as_deref())?;
+            }
+        }
 
-        emit!(OracleSwapped {
-            user: ctx.accounts.user.key(),
-            base_vault: ctx.accounts.base_vault.key(),
-            usdc_vault: ctx.accounts.usdc_vault.key(),
-            from_mint,
-            to_mint,
-            amount_in: amount,
-            amount_out,
-            oracle_price: price.price,
-            oracle_conf: price.conf,
-            oracle_expo: price.expo,
-            direction: direction as u8,
+        emit!(ChangeExecuted {
+            pending_change: ctx.accounts.pending_change.key(),
+            id: ctx.accounts.pending_change.id,
+            target,
         });
 
         Ok(())
     }
-}
 
-fn pow10_u128(exp: u32) -> Result<u128> {
-    // Bound to keep computation safe and deterministic.
-    require!(exp <= 38, TeraniumError::MathOverflow);
-    let mut v: u128 = 1;
-    for _ in 0..exp {
-        v = v.checked_mul(10).ok_or(TeraniumError::MathOverflow)?;
-    }
-    Ok(v)
-}
+    /// Drops a queued change and closes its `PendingChange` to the proposer (guardian or admin).
+    pub fn cancel_change(ctx: Context<CancelChange>) -> Result<()> {
+        let authority = ctx.accounts.authority.key();
+        let config = &ctx.accounts.protocol_config;
+        let is_admin = authority == config.admin;
+        let is_guardian = config.guardian != Pubkey::default() && authority == config.guardian;
+        require!(is_admin || is_guardian, TeraniumError::Unauthorized);
 
-fn base_to_usdc(amount_base: u128, price: u128, expo: i32, base_decimals: u32, usdc_decimals: u32) -> Result<u128> {
-    // usdc_out = amount_base * price * 10^{usdc_decimals} * 10^{max(expo,0)} / (10^{base_decimals} * 10^{max(-expo,0)})
-    let expo_pos: u32 = if expo > 0 { expo as u32 } else { 0 };
-    let expo_neg: u32 = if expo < 0 { (-expo) as u32 } else { 0 };
+        let change = ctx.accounts.pending_change.change.clone();
+        let target = change_target(
+            &change,
+            &ctx.accounts.protocol_config,
+            &ctx.accounts.pair_config,
+            &ctx.accounts.vault,
+        )?;
+        require_keys_eq!(target, ctx.accounts.pending_change.target, TeraniumError::ChangeTargetMismatch);
+        track_pending_change(&change, &mut ctx.accounts.pair_config, &mut ctx.accounts.vault, false)?;
 
-    let num = amount_base
-        .checked_mul(price)
-        .ok_or(TeraniumError::MathOverflow)?
-        .checked_mul(pow10_u128(usdc_decimals)?)
-        .ok_or(TeraniumError::MathOverflow)?
-        .checked_mul(pow10_u128(expo_pos)?)
-        .ok_or(TeraniumError::MathOverflow)?;
+        let pending = &ctx.accounts.pending_change;
+        emit!(ChangeCancelled {
+            pending_change: pending.key(),
+            id: pending.id,
+            target: pending.target,
+            authority,
+        });
 
-    let denom = pow10_u128(base_decimals)?
-        .checked_mul(pow10_u128(expo_neg)?)
-        .ok_or(TeraniumError::MathOverflow)?;
+        Ok(())
+    }
 
-    Ok(num.checked_div(denom).ok_or(TeraniumError::SwapZeroOut)?)
-}
+    /// Pauses a pair's swaps until `reset_circuit_breaker`.
+    ///
+    /// - Signed by the admin or guardian (`authority`): trips unconditionally, without reading the oracle.
+    /// - Otherwise permissionless: reads the pair's oracle(s) like a swap does, with the same staleness
+    ///   limits, and trips the breaker when either side of the price moved more than `max_price_jump_bps`
+    ///   from the last executed price within the window. Swaps trip it the same way on their own.
+    /// - The permissionless path fails with `CircuitBreakerNotTriggered` otherwise, and while a fallback
+    ///   price is active.
+    pub fn trip_circuit_breaker(ctx: Context<TripCircuitBreaker>) -> Result<()> {
+        let clock = Clock::get()?;
+        require!(!ctx.accounts.pair_config.circuit_breaker_tripped, TeraniumError::CircuitBreakerTripped);
 
-fn usdc_to_base(amount_usdc: u128, price: u128, expo: i32, base_decimals: u32, usdc_decimals: u32) -> Result<u128> {
-    // base_out = amount_usdc * 10^{base_decimals} * 10^{max(-expo,0)} / (price * 10^{usdc_decimals} * 10^{max(expo,0)})
-    let expo_pos: u32 = if expo > 0 { expo as u32 } else { 0 };
-    let expo_neg: u32 = if expo < 0 { (-expo) as u32 } else { 0 };
+        if let Some(authority) = &ctx.accounts.authority {
+            let authority = authority.key();
+            let config = &ctx.accounts.protocol_config;
+            let is_admin = authority == config.admin;
+            let is_guardian = config.guardian != Pubkey::default() && authority == config.guardian;
+            require!(is_admin || is_guardian, TeraniumError::Unauthorized);
 
-    let num = amount_usdc
-        .checked_mul(pow10_u128(base_decimals)?)
-        .ok_or(TeraniumError::MathOverflow)?
-        .checked_mul(pow10_u128(expo_neg)?)
-        .ok_or(TeraniumError::MathOverflow)?;
+            trip_pair_circuit_breaker(&mut ctx.accounts.pair_config, None, authority, clock.unix_timestamp);
+            return Ok(());
+        }
 
-    let denom = price
-        .checked_mul(pow10_u128(usdc_decimals)?)
-        .ok_or(TeraniumError::MathOverflow)?
-        .checked_mul(pow10_u128(expo_pos)?)
-        .ok_or(TeraniumError::MathOverflow)?;
+        let pair = &ctx.accounts.pair_config;
+        require!(
+            pair.fallback_expires_at <= clock.unix_timestamp,
+            TeraniumError::CircuitBreakerNotTriggered
+        );
 
-    Ok(num.checked_div(denom).ok_or(TeraniumError::SwapZeroOut)?)
-}
+        for from_base in [true, false] {
+            let price = load_pair_price(
+                pair,
+                &ctx.accounts.pyth_price_account,
+                ctx.accounts.secondary_price_account.as_deref(),
+                from_base,
+            )?;
+            check_staleness(pair, &price, &clock)?;
+            if !price_jump_exceeded(pair, &price, clock.unix_timestamp)? {
+                continue;
+            }
 
-#[derive(Accounts)]
-#[instruction(mint: Pubkey)]
-pub struct InitializeVault<'info> {
-    #[account(mut)]
-    pub payer: Signer<'info>,
+            trip_pair_circuit_breaker(
+                &mut ctx.accounts.pair_config,
+                Some(&price),
+                Pubkey::default(),
+                clock.unix_timestamp,
+            );
+            return Ok(());
+        }
 
-    pub mint: Account<'info, Mint>,
+        err!(TeraniumError::CircuitBreakerNotTriggered)
+    }
 
-    #[account(
-        init,
-        payer = payer,
-        space = 8 + VaultAccount::INIT_SPACE,
-        seeds = [b"vault", mint.key().as_ref()],
-        bump
-    )]
-    pub vault: Account<'info, VaultAccount>,
+    /// Re-enables swaps on a pair paused by the circuit breaker (admin or guardian).
+    ///
+    /// - Drops the last executed price as a breaker reference, so the next swap sets a fresh one.
+    ///   The price itself is kept as the fallback price bound.
+    pub fn reset_circuit_breaker(ctx: Context<ResetCircuitBreaker>) -> Result<()> {
+        let authority = ctx.accounts.authority.key();
+        let config = &ctx.accounts.protocol_config;
+        let is_admin = authority == config.admin;
+        let is_guardian = config.guardian != Pubkey::default() && authority == config.guardian;
+        require!(is_admin || is_guardian, TeraniumError::Unauthorized);
 
-    /// CHECK: PDA signer only; validated by seeds.
-    #[account(
-        seeds = [b"vault_authority", vault.key().as_ref()],
-        bump
-    )]
-    pub vault_authority: UncheckedAccount<'info>,
+        let pair = &mut ctx.accounts.pair_config;
+        pair.circuit_breaker_tripped = false;
+        pair.last_price_timestamp = 0;
 
-    #[account(
-        init,
-        payer = payer,
-        associated_token::mint = mint,
-        associated_token::authority = vault_authority
-    )]
-    pub vault_token_account: Account<'info, TokenAccount>,
+        emit!(CircuitBreakerReset {
+            pair_config: pair.key(),
+            authority,
+        });
 
-    pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
-    pub associated_token_program: Program<'info, AssociatedToken>,
-    pub rent: Sysvar<'info, Rent>,
-}
+        Ok(())
+    }
 
-#[derive(Accounts)]
-pub struct Deposit<'info> {
-    #[account(mut)]
-    pub owner: Signer<'info>,
+    /// Sets a manual price that replaces the oracle for `ttl_seconds` (at most 3_600).
+    ///
+    /// - Must share the exponent of the last executed oracle price and stay within
+    ///   `max_fallback_deviation_bps` of it.
+    /// - Swaps at the fallback price are capped at `max_fallback_trade_notional` and flagged in `OracleSwapped`.
+    pub fn set_pair_fallback_price(
+        ctx: Context<SetPairConfig>,
+        price: i64,
+        conf: u64,
+        expo: i32,
+        ttl_seconds: u32,
+    ) -> Result<()> {
+        let pair = &mut ctx.accounts.pair_config;
+        check_fallback_price(pair, price, expo, ttl_seconds)?;
+
+        let expires_at = Clock::get()?
+            .unix_timestamp
+            .checked_add(ttl_seconds as i64)
+            .ok_or(TeraniumError::MathOverflow)?;
+        pair.fallback_price = price;
+        pair.fallback_conf = conf;
+        pair.fallback_expo = expo;
+        pair.fallback_expires_at = expires_at;
+
+        emit!(PairFallbackPriceSet {
+            pair_config: pair.key(),
+            price,
+            conf,
+            expo,
+            expires_at,
+            reference_price: pair.last_price,
+        });
+
+        Ok(())
+    }
+
+    /// Ends an active fallback price early; swaps go back to the oracle.
+    pub fn clear_pair_fallback_price(ctx: Context<SetPairConfig>) -> Result<()> {
+        let pair = &mut ctx.accounts.pair_config;
+        pair.fallback_expires_at = 0;
+
+        emit!(PairFallbackPriceCleared {
+            pair_config: pair.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Sends a vault's accrued swap fees to a treasury token account (PDA-signed).
+    pub fn collect_fees<'info>(ctx: Context<'_, '_, '_, 'info, CollectFees<'info>>) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        require_keys_eq!(ctx.accounts.vault_token_account.mint, vault.mint, TeraniumError::MintMismatch);
+        require_keys_eq!(ctx.accounts.treasury_token_account.mint, vault.mint, TeraniumError::MintMismatch);
+
+        let amount = vault.accrued_fees;
+        require!(amount > 0, TeraniumError::NoFeesToCollect);
+
+        let vault_key = vault.key();
+        let authority_seeds: &[&[u8]] = &[
+            b"vault_authority",
+            vault_key.as_ref(),
+            &[vault.authority_bump],
+        ];
+
+        transfer_checked(
+            &ctx.accounts.token_program,
+            ctx.accounts.vault_token_account.to_account_info(),
+            &ctx.accounts.mint,
+            ctx.accounts.treasury_token_account.to_account_info(),
+            ctx.accounts.vault_authority.to_account_info(),
+            ctx.remaining_accounts,
+            amount,
+            &[authority_seeds],
+        )?;
+
+        vault.accrued_fees = 0;
+
+        emit!(FeesCollected {
+            vault: vault_key,
+            treasury_token_account: ctx.accounts.treasury_token_account.key(),
+            amount,
+        });
+
+        Ok(())
+    }
+
+    /// Puts a vault into withdraw-only mode ahead of `close_vault` (admin-only, irreversible).
+    ///
+    /// - Deposits and swaps touching the vault fail with `VaultDecommissioned`.
+    /// - Withdrawals and fee collection keep working, subject to the usual pause flags.
+    pub fn decommission_vault(ctx: Context<DecommissionVault>) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        require!(!vault.decommissioned, TeraniumError::VaultDecommissioned);
+        vault.decommissioned = true;
+
+        emit!(VaultDecommissioned {
+            vault: vault.key(),
+            total_deposits: vault.total_deposits,
+            total_shares: vault.total_shares,
+        });
+
+        Ok(())
+    }
+
+    /// Closes a pair config (admin-only); its rent goes to the admin.
+    ///
+    /// - Fails with `PairInUse` while a change targeting the pair is queued.
+    /// - Releases the pair from both vaults' `pair_count`, which `close_vault` requires to be zero.
+    pub fn close_pair(ctx: Context<ClosePair>) -> Result<()> {
+        require!(ctx.accounts.pair_config.pending_changes == 0, TeraniumError::PairInUse);
+
+        for vault in [&mut ctx.accounts.base_vault, &mut ctx.accounts.quote_vault] {
+            vault.pair_count = vault.pair_count.checked_sub(1).ok_or(TeraniumError::MathOverflow)?;
+        }
+
+        emit!(PairClosed {
+            pair_config: ctx.accounts.pair_config.key(),
+            base_vault: ctx.accounts.base_vault.key(),
+            quote_vault: ctx.accounts.quote_vault.key(),
+        });
+
+        Ok(())
+    }
+
+    /// Retires a decommissioned vault once depositors have withdrawn (admin-only).
+    ///
+    /// - Requires `decommission_vault` first (`VaultNotDecommissioned`) and no shares left (`VaultNotEmpty`);
+    ///   LP assets left without shares (rounding dust, swap PnL after the last exit) are booked as fees.
+    /// - Requires every pair on the vault to be closed through `close_pair` and no queued vault change
+    ///   (`VaultInUse`), so nothing carries over to a vault later re-created for the mint.
+    /// - Sweeps the whole token balance (unclaimed fees, donations, rounding dust) to a treasury token account,
+    ///   then closes the vault token account and the vault PDA; their rent goes to the admin.
+    pub fn close_vault<'info>(ctx: Context<'_, '_, '_, 'info, CloseVault<'info>>) -> Result<()> {
+        let vault = &mut ctx.accounts.vault;
+        prepare_close_vault(vault)?;
+        require_keys_eq!(ctx.accounts.treasury_token_account.mint, vault.mint, TeraniumError::MintMismatch);
+
+        let vault_key = vault.key();
+        let authority_seeds: &[&[u8]] = &[
+            b"vault_authority",
+            vault_key.as_ref(),
+            &[vault.authority_bump],
+        ];
+
+        let swept = ctx.accounts.vault_token_account.amount;
+        if swept > 0 {
+            transfer_checked(
+                &ctx.accounts.token_program,
+                ctx.accounts.vault_token_account.to_account_info(),
+                &ctx.accounts.mint,
+                ctx.accounts.treasury_token_account.to_account_info(),
+                ctx.accounts.vault_authority.to_account_info(),
+                ctx.remaining_accounts,
+                swept,
+                &[authority_seeds],
+            )?;
+        }
+
+        token_interface::close_account(CpiContext::new_with_signer(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.vault_token_account.to_account_info(),
+                destination: ctx.accounts.admin.to_account_info(),
+                authority: ctx.accounts.vault_authority.to_account_info(),
+            },
+            &[authority_seeds],
+        ))?;
+
+        emit!(VaultClosed {
+            vault: vault_key,
+            mint: vault.mint,
+            treasury_token_account: ctx.accounts.treasury_token_account.key(),
+            swept,
+        });
+
+        Ok(())
+    }
+
+    /// Oracle-priced swap between a base mint vault and the quote vault.
+    ///
+    /// - Prices the base in the pair's quote asset through the oracle bound in the pair config,
+    ///   cross-checked against the secondary oracle and EMA guard when configured.
+    /// - Blocked while swaps are paused globally or on either vault.
+    /// - Uses oracle confidence interval as a deterministic slippage bound.
+    /// - Prices at the oracle mid, or at a confidence-adjusted bid/ask depending on the pair pricing mode.
+    /// - Enforces the pair's staleness limits (publish time and/or slot).
+    /// - A price jump past the circuit breaker threshold trips the breaker and returns without trading.
+    /// - Takes no deadline; use `oracle_swap_v2` for one.
+    /// - Charges the pair fee on `amount_out`; the fee stays in the paying vault as `accrued_fees`.
+    /// - Books the input to the receiving vault's LP assets (`total_deposits`) and the gross
+    ///   output against the paying vault's LP assets, so depositors carry swap PnL.
+    /// - Ensures vaults remain solvent against `total_deposits` plus accrued fees after swap.
+    pub fn oracle_swap<'info>(
+        ctx: Context<'_, '_, '_, 'info, OracleSwap<'info>>,
+        amount: u64,
+        max_slippage_bps: u16,
+    ) -> Result<()> {
+        let swap = SwapAmount::ExactIn {
+            amount_in: amount,
+            min_amount_out: 0,
+        };
+        process_oracle_swap(ctx.accounts, None, ctx.remaining_accounts, max_slippage_bps, swap, None)
+    }
+
+    /// Same as `oracle_swap`, but fails with `SlippageToleranceExceeded` when the amount the
+    /// user receives (after fees) is below `min_amount_out`, and with `SwapExpired` once the
+    /// optional `deadline` has passed.
+    pub fn oracle_swap_v2<'info>(
+        ctx: Context<'_, '_, '_, 'info, OracleSwap<'info>>,
+        amount: u64,
+        max_slippage_bps: u16,
+        min_amount_out: u64,
+        deadline: Option<SwapDeadline>,
+    ) -> Result<()> {
+        let swap = SwapAmount::ExactIn {
+            amount_in: amount,
+            min_amount_out,
+        };
+        process_oracle_swap(ctx.accounts, None, ctx.remaining_accounts, max_slippage_bps, swap, deadline)
+    }
+
+    /// Exact-output variant of `oracle_swap`: the user receives exactly `amount_out` (after fees).
+    ///
+    /// - The input is computed with the inverse of the exact-in math, rounded up (vault favor).
+    /// - Fails with `SlippageToleranceExceeded` when the required input exceeds `max_amount_in`.
+    /// - Fails with `SwapExpired` once the optional `deadline` has passed.
+    /// - Runs the same pause, oracle binding, staleness, confidence and solvency checks.
+    pub fn oracle_swap_exact_out<'info>(
+        ctx: Context<'_, '_, '_, 'info, OracleSwap<'info>>,
+        amount_out: u64,
+        max_amount_in: u64,
+        max_slippage_bps: u16,
+        deadline: Option<SwapDeadline>,
+    ) -> Result<()> {
+        let swap = SwapAmount::ExactOut {
+            amount_out,
+            max_amount_in,
+        };
+        process_oracle_swap(ctx.accounts, None, ctx.remaining_accounts, max_slippage_bps, swap, deadline)
+    }
+
+    /// `oracle_swap_v2` with native SOL on one side.
+    ///
+    /// - The native side's user token account is omitted; a temporary `["wrap", user]` native token account
+    ///   (authority = user) stands in for it and is closed to the user within the instruction.
+    /// - Selling SOL: wraps `amount` lamports into the wrap account before the swap.
+    /// - Buying SOL: the output lands in the wrap account and is unwrapped to the user with its rent.
+    /// - Wrapped SOL the user already holds is never touched. Fails with `NotNativeMint` unless exactly one
+    ///   user token account is omitted.
+    pub fn oracle_swap_sol<'info>(
+        ctx: Context<'_, '_, '_, 'info, OracleSwapSol<'info>>,
+        amount: u64,
+        max_slippage_bps: u16,
+        min_amount_out: u64,
+        deadline: Option<SwapDeadline>,
+    ) -> Result<()> {
+        let accounts = &mut ctx.accounts.swap;
+        let wrap = accounts.user_from_token_account.is_none();
+        require!(wrap != accounts.user_to_token_account.is_none(), TeraniumError::NotNativeMint);
+
+        if wrap {
+            system_program::transfer(
+                CpiContext::new(
+                    ctx.accounts.system_program.to_account_info(),
+                    system_program::Transfer {
+                        from: accounts.user.to_account_info(),
+                        to: ctx.accounts.wrap_token_account.to_account_info(),
+                    },
+                ),
+                amount,
+            )?;
+            token_interface::sync_native(CpiContext::new(
+                ctx.accounts.token_program.to_account_info(),
+                SyncNative {
+                    account: ctx.accounts.wrap_token_account.to_account_info(),
+                },
+            ))?;
+            ctx.accounts.wrap_token_account.reload()?;
+        }
+
+        let swap = SwapAmount::ExactIn {
+            amount_in: amount,
+            min_amount_out,
+        };
+        process_oracle_swap(
+            accounts,
+            Some(&ctx.accounts.wrap_token_account),
+            ctx.remaining_accounts,
+            max_slippage_bps,
+            swap,
+            deadline,
+        )?;
+
+        token_interface::close_account(CpiContext::new(
+            ctx.accounts.token_program.to_account_info(),
+            CloseAccount {
+                account: ctx.accounts.wrap_token_account.to_account_info(),
+                destination: accounts.user.to_account_info(),
+                authority: accounts.user.to_account_info(),
+            },
+        ))?;
+
+        Ok(())
+    }
+}
+
+/// Pause, position and cap checks shared by `deposit` and `deposit_sol`; claims a freshly created position.
+fn prepare_deposit(
+    protocol_config: &ProtocolConfig,
+    vault: &Account<VaultAccount>,
+    position: &mut UserPosition,
+    owner: Pubkey,
+    amount: u64,
+) -> Result<()> {
+    require!(amount > 0, TeraniumError::InvalidAmount);
+
+    let paused = protocol_config.pause_flags | vault.pause_flags;
+    require!(paused & PAUSE_DEPOSITS == 0, TeraniumError::PausedDeposits);
+    require!(!vault.decommissioned, TeraniumError::VaultDecommissioned);
+
+    if position.owner == Pubkey::default() {
+        position.owner = owner;
+        position.vault = vault.key();
+        position.deposited = 0;
+        position.shares = 0;
+    }
+
+    require_keys_eq!(position.owner, owner, TeraniumError::Unauthorized);
+    require_keys_eq!(position.vault, vault.key(), TeraniumError::InvalidUserPosition);
+
+    // Guarded launches: the vault-wide and per-user caps apply to principal, not swap PnL (0 = unlimited).
+    // The error carries (amount, headroom) as its compared values.
+    let mut headroom = u64::MAX;
+    if vault.max_total_deposits > 0 {
+        headroom = headroom.min(vault.max_total_deposits.saturating_sub(vault.total_principal));
+    }
+    if vault.max_deposit_per_user > 0 {
+        headroom = headroom.min(vault.max_deposit_per_user.saturating_sub(position.deposited));
+    }
+    if amount > headroom {
+        return Err(error!(TeraniumError::DepositCapExceeded).with_values((amount, headroom)));
+    }
+
+    Ok(())
+}
+
+/// Mints shares for `received` at the current exchange rate and books it as principal.
+fn credit_deposit(
+    vault: &mut Account<VaultAccount>,
+    position: &mut UserPosition,
+    owner: Pubkey,
+    received: u64,
+) -> Result<()> {
+    // Orphaned assets must not be claimed by the next depositor's 1:1 shares.
+    reclaim_orphaned_assets(vault)?;
+
+    // Shares are minted at the current exchange rate, rounded down (in the vault's favor).
+    let shares = if vault.total_shares == 0 {
+        received
+    } else {
+        require!(vault.total_deposits > 0, TeraniumError::VaultDepleted);
+        mul_div_u64(received, vault.total_shares, vault.total_deposits, false)?
+    };
+    require!(shares > 0, TeraniumError::ZeroShares);
+
+    position.shares = position.shares.checked_add(shares).ok_or(TeraniumError::MathOverflow)?;
+
+    position.deposited = position
+        .deposited
+        .checked_add(received)
+        .ok_or(TeraniumError::MathOverflow)?;
+
+    vault.total_deposits = vault
+        .total_deposits
+        .checked_add(received)
+        .ok_or(TeraniumError::MathOverflow)?;
+
+    vault.total_principal = vault
+        .total_principal
+        .checked_add(received)
+        .ok_or(TeraniumError::MathOverflow)?;
+
+    vault.total_shares = vault
+        .total_shares
+        .checked_add(shares)
+        .ok_or(TeraniumError::MathOverflow)?;
+
+    emit!(Deposited {
+        owner,
+        vault: vault.key(),
+        amount: received,
+        deposited_after: position.deposited,
+        total_deposits_after: vault.total_deposits,
+        shares,
+        shares_after: position.shares,
+        total_shares_after: vault.total_shares,
+    });
+
+    Ok(())
+}
+
+/// Assets left behind with no shares outstanding (rounding dust, swap PnL after the last LP exited) belong to
+/// nobody; moves them to protocol fees.
+fn reclaim_orphaned_assets(vault: &mut Account<VaultAccount>) -> Result<()> {
+    if vault.total_shares == 0 && vault.total_deposits > 0 {
+        let orphaned = vault.total_deposits;
+        vault.accrued_fees = vault
+            .accrued_fees
+            .checked_add(orphaned)
+            .ok_or(TeraniumError::MathOverflow)?;
+        vault.total_deposits = 0;
+
+        emit!(OrphanedAssetsReclaimed {
+            vault: vault.key(),
+            amount: orphaned,
+            accrued_fees_after: vault.accrued_fees,
+        });
+    }
+
+    Ok(())
+}
+
+/// Burns a position's shares when they are worth less than one token unit (`PositionNotEmpty` otherwise).
+/// Their value stays in `total_deposits` for the remaining LPs, or is reclaimed as fees once none are left.
+fn forfeit_dust_shares(vault: &mut Account<VaultAccount>, position: &mut UserPosition) -> Result<()> {
+    require_keys_eq!(position.vault, vault.key(), TeraniumError::InvalidUserPosition);
+    let value = mul_div_u64(position.shares, vault.total_deposits, vault.total_shares, false)?;
+    require!(value == 0, TeraniumError::PositionNotEmpty);
+
+    vault.total_shares = vault
+        .total_shares
+        .checked_sub(position.shares)
+        .ok_or(TeraniumError::MathOverflow)?;
+
+    vault.total_principal = vault
+        .total_principal
+        .checked_sub(position.deposited)
+        .ok_or(TeraniumError::MathOverflow)?;
+
+    emit!(PositionDustForfeited {
+        owner: position.owner,
+        vault: vault.key(),
+        shares: position.shares,
+        principal: position.deposited,
+        total_shares_after: vault.total_shares,
+    });
+
+    position.shares = 0;
+    position.deposited = 0;
+
+    Ok(())
+}
+
+/// Lifecycle checks for `close_vault`; reclaims orphaned assets so the sweep books them as fees.
+fn prepare_close_vault(vault: &mut Account<VaultAccount>) -> Result<()> {
+    require!(vault.decommissioned, TeraniumError::VaultNotDecommissioned);
+    require!(vault.total_shares == 0, TeraniumError::VaultNotEmpty);
+    require!(vault.pair_count == 0 && vault.pending_changes == 0, TeraniumError::VaultInUse);
+    reclaim_orphaned_assets(vault)
+}
+
+/// Pause and position checks shared by `withdraw` and `withdraw_sol`; returns the shares to burn
+/// for `amount` and the principal they carry.
+fn prepare_withdraw(
+    protocol_config: &ProtocolConfig,
+    vault: &Account<VaultAccount>,
+    position: &UserPosition,
+    owner: Pubkey,
+    amount: u64,
+) -> Result<(u64, u64)> {
+    require!(amount > 0, TeraniumError::InvalidAmount);
+
+    let paused = protocol_config.pause_flags | vault.pause_flags;
+    require!(paused & PAUSE_WITHDRAWALS == 0, TeraniumError::PausedWithdrawals);
+
+    require_keys_eq!(position.owner, owner, TeraniumError::Unauthorized);
+    require_keys_eq!(position.vault, vault.key(), TeraniumError::InvalidUserPosition);
+
+    // Shares are burned at the current exchange rate, rounded up (in the vault's favor).
+    require!(vault.total_deposits > 0, TeraniumError::VaultDepleted);
+    let shares = mul_div_u64(amount, vault.total_shares, vault.total_deposits, true)?;
+    require!(shares > 0, TeraniumError::ZeroShares);
+    require!(shares <= position.shares, TeraniumError::InsufficientDepositedBalance);
+
+    // Principal basis is reduced pro rata to the shares burned.
+    let principal = mul_div_u64(position.deposited, shares, position.shares, false)?;
+
+    Ok((shares, principal))
+}
+
+/// Burns `shares` and removes `amount` from the vault's LP assets after the tokens left the vault.
+fn debit_withdraw(
+    vault: &mut Account<VaultAccount>,
+    position: &mut UserPosition,
+    owner: Pubkey,
+    amount: u64,
+    shares: u64,
+    principal: u64,
+) -> Result<()> {
+    position.deposited = position
+        .deposited
+        .checked_sub(principal)
+        .ok_or(TeraniumError::MathOverflow)?;
+
+    position.shares = position.shares.checked_sub(shares).ok_or(TeraniumError::MathOverflow)?;
+
+    vault.total_deposits = vault
+        .total_deposits
+        .checked_sub(amount)
+        .ok_or(TeraniumError::MathOverflow)?;
+
+    vault.total_principal = vault
+        .total_principal
+        .checked_sub(principal)
+        .ok_or(TeraniumError::MathOverflow)?;
+
+    vault.total_shares = vault
+        .total_shares
+        .checked_sub(shares)
+        .ok_or(TeraniumError::MathOverflow)?;
+
+    emit!(Withdrawn {
+        owner,
+        vault: vault.key(),
+        amount,
+        deposited_after: position.deposited,
+        total_deposits_after: vault.total_deposits,
+        shares,
+        shares_after: position.shares,
+        total_shares_after: vault.total_shares,
+    });
+
+    Ok(())
+}
+
+/// Closes `position` to its owner; a later deposit recreates it through `init_if_needed`.
+fn close_position_account<'info>(position: &Account<'info, UserPosition>, owner: &Signer<'info>) -> Result<()> {
+    emit!(PositionClosed {
+        owner: owner.key(),
+        vault: position.vault,
+        user_position: position.key(),
+    });
+    position.close(owner.to_account_info())
+}
+
+/// Reads the pair's oracle price, combined with the secondary oracle when the pair has one.
+///
+/// The primary account owner is bound by the account constraint, the rest by the pair config.
+/// `from_base` picks the side of a conservative dual-oracle price.
+fn load_pair_price(
+    pair: &PairConfig,
+    price_account: &AccountInfo,
+    secondary_price_account: Option<&AccountInfo>,
+    from_base: bool,
+) -> Result<oracle::OraclePrice> {
+    let price = oracle::load_price(&pair.primary_oracle(), price_account)?;
+    if !pair.secondary_oracle.is_set() {
+        return Ok(price);
+    }
+
+    let secondary_account = secondary_price_account.ok_or(TeraniumError::OracleAccountMismatch)?;
+    require_keys_eq!(*secondary_account.owner, pair.secondary_oracle.owner, TeraniumError::OracleOwnerMismatch);
+    let secondary = oracle::load_price(&pair.secondary_oracle, secondary_account)?;
+    oracle::combine(
+        price,
+        secondary,
+        pair.max_oracle_deviation_bps,
+        pair.dual_oracle_pricing,
+        from_base,
+    )
+}
+
+/// Staleness enforcement against the pair's limits (0 disables a check; at least one is set).
+fn check_staleness(pair: &PairConfig, price: &oracle::OraclePrice, clock: &Clock) -> Result<()> {
+    if pair.max_staleness_seconds > 0 {
+        let age = clock
+            .unix_timestamp
+            .checked_sub(price.publish_time)
+            .ok_or(TeraniumError::OracleStale)?;
+        require!(age <= pair.max_staleness_seconds as i64, TeraniumError::OracleStale);
+    }
+    if pair.max_staleness_slots > 0 {
+        let slot_age = clock.slot.saturating_sub(price.publish_slot);
+        require!(slot_age <= pair.max_staleness_slots, TeraniumError::OracleStale);
+    }
+    Ok(())
+}
+
+/// Whether `price` moved more than `max_price_jump_bps` from the last executed oracle price.
+///
+/// Only applies while that price is within `circuit_breaker_window_seconds` and shares the exponent.
+fn price_jump_exceeded(pair: &PairConfig, price: &oracle::OraclePrice, now: i64) -> Result<bool> {
+    if pair.max_price_jump_bps == 0
+        || pair.last_price_timestamp == 0
+        || pair.last_price_expo != price.expo
+        || now.saturating_sub(pair.last_price_timestamp) > pair.circuit_breaker_window_seconds as i64
+    {
+        return Ok(false);
+    }
+
+    let spot = price.price as i128;
+    let last = pair.last_price as i128;
+    // |spot - last| * 10_000 > last * max_price_jump_bps
+    Ok((spot - last).abs().checked_mul(BPS_DENOM).ok_or(TeraniumError::MathOverflow)?
+        > last
+            .checked_mul(pair.max_price_jump_bps as i128)
+            .ok_or(TeraniumError::MathOverflow)?)
+}
+
+/// Validates an admin fallback price: fallback prices enabled, a positive price, a TTL of at most
+/// `MAX_FALLBACK_PRICE_TTL_SECONDS`, and within `max_fallback_deviation_bps` of the last oracle price.
+fn check_fallback_price(pair: &PairConfig, price: i64, expo: i32, ttl_seconds: u32) -> Result<()> {
+    require!(pair.max_fallback_deviation_bps > 0, TeraniumError::FallbackPriceDisabled);
+    require!(
+        price > 0 && ttl_seconds > 0 && ttl_seconds <= MAX_FALLBACK_PRICE_TTL_SECONDS,
+        TeraniumError::InvalidFallbackPrice
+    );
+    require!(
+        pair.last_price > 0 && pair.last_price_expo == expo,
+        TeraniumError::FallbackReferenceUnavailable
+    );
+
+    let last = pair.last_price as i128;
+    // |price - last| * 10_000 <= last * max_fallback_deviation_bps
+    require!(
+        (price as i128 - last).abs().checked_mul(BPS_DENOM).ok_or(TeraniumError::MathOverflow)?
+            <= last
+                .checked_mul(pair.max_fallback_deviation_bps as i128)
+                .ok_or(TeraniumError::MathOverflow)?,
+        TeraniumError::FallbackPriceDeviation
+    );
+    Ok(())
+}
+
+/// The admin fallback price as a fresh reading, or `None` once `fallback_expires_at` has passed.
+fn active_fallback_price(pair: &PairConfig, clock: &Clock) -> Option<oracle::OraclePrice> {
+    if pair.fallback_expires_at <= clock.unix_timestamp {
+        return None;
+    }
+    Some(oracle::OraclePrice {
+        price: pair.fallback_price,
+        conf: pair.fallback_conf,
+        expo: pair.fallback_expo,
+        publish_time: clock.unix_timestamp,
+        publish_slot: clock.slot,
+        ema_price: None,
+    })
+}
+
+/// Per-swap size limits: `max_trade_notional` (0 = unlimited), and `max_fallback_trade_notional`
+/// while the swap is priced by a fallback price.
+fn check_trade_notional(pair: &PairConfig, notional: u64, fallback: bool) -> Result<()> {
+    require!(
+        pair.max_trade_notional == 0 || notional <= pair.max_trade_notional,
+        TeraniumError::TradeSizeExceeded
+    );
+    require!(
+        !fallback || notional <= pair.max_fallback_trade_notional,
+        TeraniumError::TradeSizeExceeded
+    );
+    Ok(())
+}
+
+/// Pauses the pair's swaps until `reset_circuit_breaker`. `price` is the reading that jumped, or `None`
+/// when `authority` (the admin or guardian) tripped the breaker by hand.
+fn trip_pair_circuit_breaker(
+    pair: &mut Account<PairConfig>,
+    price: Option<&oracle::OraclePrice>,
+    authority: Pubkey,
+    now: i64,
+) {
+    pair.circuit_breaker_tripped = true;
+
+    emit!(CircuitBreakerTripped {
+        pair_config: pair.key(),
+        last_price: pair.last_price,
+        last_price_timestamp: pair.last_price_timestamp,
+        price: price.map_or(0, |price| price.price),
+        expo: price.map_or(0, |price| price.expo),
+        timestamp: now,
+        authority,
+    });
+}
+
+/// Which side of a swap the user fixed.
+enum SwapAmount {
+    ExactIn { amount_in: u64, min_amount_out: u64 },
+    ExactOut { amount_out: u64, max_amount_in: u64 },
+}
+
+fn process_oracle_swap<'info>(
+    accounts: &mut OracleSwap<'info>,
+    wrap_token_account: Option<&InterfaceAccount<'info, TokenAccount>>,
+    remaining_accounts: &[AccountInfo<'info>],
+    max_slippage_bps: u16,
+    swap: SwapAmount,
+    deadline: Option<SwapDeadline>,
+) -> Result<()> {
+    match swap {
+        SwapAmount::ExactIn { amount_in, .. } => require!(amount_in > 0, TeraniumError::InvalidAmount),
+        SwapAmount::ExactOut { amount_out, .. } => require!(amount_out > 0, TeraniumError::InvalidAmount),
+    }
+    require!(max_slippage_bps as i128 <= BPS_DENOM, TeraniumError::InvalidSlippageBps);
+
+    // The deadline is inclusive: a swap landing at exactly the deadline still fills.
+    let clock = Clock::get()?;
+    match deadline {
+        Some(SwapDeadline::UnixTimestamp { unix_timestamp }) => {
+            require!(clock.unix_timestamp <= unix_timestamp, TeraniumError::SwapExpired)
+        }
+        Some(SwapDeadline::Slot { slot }) => require!(clock.slot <= slot, TeraniumError::SwapExpired),
+        None => {}
+    }
+
+    let paused = accounts.protocol_config.pause_flags
+        | accounts.base_vault.pause_flags
+        | accounts.quote_vault.pause_flags;
+    require!(paused & PAUSE_SWAPS == 0, TeraniumError::PausedSwaps);
+    require!(
+        !accounts.base_vault.decommissioned && !accounts.quote_vault.decommissioned,
+        TeraniumError::VaultDecommissioned
+    );
+    require!(!accounts.pair_config.circuit_breaker_tripped, TeraniumError::CircuitBreakerTripped);
+
+    require_keys_eq!(accounts.quote_mint.key(), accounts.pair_config.quote_mint, TeraniumError::InvalidQuoteMint);
+    require_keys_eq!(accounts.quote_vault.mint, accounts.quote_mint.key(), TeraniumError::MintMismatch);
+    require_keys_eq!(accounts.base_vault.mint, accounts.base_mint.key(), TeraniumError::MintMismatch);
+    require!(accounts.base_vault.mint != accounts.quote_mint.key(), TeraniumError::InvalidSwapPair);
+
+    require_keys_eq!(accounts.base_vault_token_account.mint, accounts.base_vault.mint, TeraniumError::MintMismatch);
+    require_keys_eq!(accounts.quote_vault_token_account.mint, accounts.quote_vault.mint, TeraniumError::MintMismatch);
+
+    // `oracle_swap_sol` omits the user's token account on the native side and passes its wrap account instead.
+    let user_from = accounts
+        .user_from_token_account
+        .as_ref()
+        .or(wrap_token_account)
+        .ok_or(TeraniumError::InvalidSwapPair)?
+        .clone();
+    let user_to = accounts
+        .user_to_token_account
+        .as_ref()
+        .or(wrap_token_account)
+        .ok_or(TeraniumError::InvalidSwapPair)?
+        .clone();
+    let from_mint = user_from.mint;
+    let to_mint = user_to.mint;
+
+    // Determine direction from token account mints.
+
+    let base_mint = accounts.base_mint.key();
+    let quote_mint = accounts.quote_mint.key();
+
+    let base_decimals = accounts.base_mint.decimals as u32;
+    let quote_decimals = accounts.quote_mint.decimals as u32;
+
+    require!(
+        (from_mint == base_mint && to_mint == quote_mint) || (from_mint == quote_mint && to_mint == base_mint),
+        TeraniumError::InvalidSwapPair
+    );
+
+    let from_base = from_mint == base_mint;
+    if from_base {
+        check_transfer_hook(&accounts.base_vault, &accounts.base_mint)?;
+    } else {
+        check_transfer_hook(&accounts.quote_vault, &accounts.quote_mint)?;
+    }
+
+    let pair = &accounts.pair_config;
+    // An active admin fallback price replaces the oracle(s) until it expires.
+    let fallback_price = active_fallback_price(pair, &clock);
+    let fallback = fallback_price.is_some();
+    let price = match fallback_price {
+        Some(price) => price,
+        None => load_pair_price(
+            pair,
+            &accounts.pyth_price_account,
+            accounts.secondary_price_account.as_deref(),
+            from_base,
+        )?,
+    };
+    check_staleness(&accounts.pair_config, &price, &clock)?;
+
+    // Confidence-based slippage bound (conf/|price| <= max_slippage_bps).
+    let px_i128: i128 = price.price as i128;
+    require!(px_i128 != 0, TeraniumError::OracleInvalidPrice);
+    require!(px_i128 > 0, TeraniumError::OracleInvalidPrice);
+
+    let abs_px: i128 = px_i128;
+    let conf_i128: i128 = price.conf as i128;
+    let max_bps: i128 = max_slippage_bps as i128;
+
+    require!(conf_i128 >= 0, TeraniumError::OracleInvalidConfidence);
+
+    // conf * 10_000 <= price * max_slippage_bps
+    require!(
+        conf_i128
+            .checked_mul(BPS_DENOM)
+            .ok_or(TeraniumError::MathOverflow)?
+            <= abs_px
+                .checked_mul(max_bps)
+                .ok_or(TeraniumError::MathOverflow)?,
+        TeraniumError::OracleSlippageExceeded
+    );
+
+    // Optional EMA guard: spot must stay within max_ema_deviation_bps of the EMA.
+    // Fallback prices have no EMA and are bounded when set instead.
+    let max_ema_deviation_bps = accounts.pair_config.max_ema_deviation_bps;
+    if max_ema_deviation_bps > 0 && !fallback {
+        let ema = price.ema_price.ok_or(TeraniumError::OracleEmaUnavailable)? as i128;
+        require!(ema > 0, TeraniumError::OracleEmaUnavailable);
+        // |spot - ema| * 10_000 <= ema * max_ema_deviation_bps
+        require!(
+            (abs_px - ema).abs().checked_mul(BPS_DENOM).ok_or(TeraniumError::MathOverflow)?
+                <= ema
+                    .checked_mul(max_ema_deviation_bps as i128)
+                    .ok_or(TeraniumError::MathOverflow)?,
+            TeraniumError::OracleEmaDeviation
+        );
+    }
+
+    let expo: i32 = price.expo;
+
+    // Circuit breaker: a jump from the last executed oracle price within the window pauses the pair until
+    // the admin or guardian resets it. The swap succeeds without trading so the pause is stored.
+    if !fallback && price_jump_exceeded(&accounts.pair_config, &price, clock.unix_timestamp)? {
+        trip_pair_circuit_breaker(&mut accounts.pair_config, Some(&price), Pubkey::default(), clock.unix_timestamp);
+        return Ok(());
+    }
+
+    // The vault buys base when the user sells it (bid) and sells base otherwise (ask).
+    let px = execution_price(
+        abs_px as u128,
+        price.conf as u128,
+        accounts.pair_config.pricing_mode,
+        accounts.pair_config.conf_multiplier_bps,
+        from_base,
+    )?;
+
+    let fee_bps = accounts.pair_config.fee_bps;
+
+    // Token-2022 transfer fees apply on both legs: the vault prices what it receives, and the user's
+    // minimum / exact output is what lands in their account.
+    let (in_mint, out_mint) = if from_base {
+        (&accounts.base_mint, &accounts.quote_mint)
+    } else {
+        (&accounts.quote_mint, &accounts.base_mint)
+    };
+
+    // Resolve what the user sends, what the vault receives, the gross output, what the vault sends
+    // and what the user receives; the vault keeps gross - sent as fee.
+    let (amount, received, gross_out, sent, amount_out) = match swap {
+        SwapAmount::ExactIn {
+            amount_in,
+            min_amount_out,
+        } => {
+            let received = amount_in
+                .checked_sub(transfer_fee(in_mint, amount_in, clock.epoch)?)
+                .ok_or(TeraniumError::MathOverflow)?;
+            let gross_out = if from_base {
+                base_to_quote(received as u128, px, expo, base_decimals, quote_decimals, false)?
+            } else {
+                quote_to_base(received as u128, px, expo, base_decimals, quote_decimals, false)?
+            };
+            let gross_out = u64::try_from(gross_out).map_err(|_| TeraniumError::MathOverflow)?;
+            let sent = gross_out
+                .checked_sub(swap_fee(gross_out, fee_bps)?)
+                .ok_or(TeraniumError::MathOverflow)?;
+            let amount_out = sent
+                .checked_sub(transfer_fee(out_mint, sent, clock.epoch)?)
+                .ok_or(TeraniumError::MathOverflow)?;
+            require!(amount_out > 0, TeraniumError::SwapZeroOut);
+            require!(amount_out >= min_amount_out, TeraniumError::SlippageToleranceExceeded);
+            (amount_in, received, gross_out, sent, amount_out)
+        }
+        SwapAmount::ExactOut {
+            amount_out,
+            max_amount_in,
+        } => {
+            // Inverse of the exact-in math, rounding the required input up.
+            let sent = amount_out
+                .checked_add(transfer_fee_inverse(out_mint, amount_out, clock.epoch)?)
+                .ok_or(TeraniumError::MathOverflow)?;
+            let gross_out = gross_out_for_net(sent, fee_bps)?;
+            let received = if from_base {
+                quote_to_base(gross_out as u128, px, expo, base_decimals, quote_decimals, true)?
+            } else {
+                base_to_quote(gross_out as u128, px, expo, base_decimals, quote_decimals, true)?
+            };
+            let received = u64::try_from(received).map_err(|_| TeraniumError::MathOverflow)?;
+            let amount_in = received
+                .checked_add(transfer_fee_inverse(in_mint, received, clock.epoch)?)
+                .ok_or(TeraniumError::MathOverflow)?;
+            require!(amount_in <= max_amount_in, TeraniumError::SlippageToleranceExceeded);
+            (amount_in, received, gross_out, sent, amount_out)
+        }
+    };
+    let fee = gross_out.checked_sub(sent).ok_or(TeraniumError::MathOverflow)?;
+
+    // Per-trade and rolling-window caps, on the quote leg of the trade.
+    let notional = if from_base { gross_out } else { received };
+    check_trade_notional(&accounts.pair_config, notional, fallback)?;
+    record_volume(&mut accounts.pair_config, clock.unix_timestamp, from_base, notional)?;
+
+    // Only oracle prices become the breaker reference and the fallback bound.
+    if !fallback {
+        let pair = &mut accounts.pair_config;
+        pair.last_price = price.price;
+        pair.last_price_expo = expo;
+        pair.last_price_timestamp = clock.unix_timestamp;
+    }
+
+    let direction = if from_base {
+        // base -> quote
+        // The gross output is paid from LP assets; the fee moves from LP assets to accrued fees.
+        let lp_assets_after = accounts
+            .quote_vault
+            .total_deposits
+            .checked_sub(gross_out)
+            .ok_or(TeraniumError::InsufficientVaultLiquidity)?;
+        let fees_after = accounts
+            .quote_vault
+            .accrued_fees
+            .checked_add(fee)
+            .ok_or(TeraniumError::MathOverflow)?;
+
+        // Ensure quote vault remains solvent against deposits and accrued fees after paying out.
+        let post = accounts
+            .quote_vault_token_account
+            .amount
+            .checked_sub(sent)
+            .ok_or(TeraniumError::InsufficientVaultLiquidity)?;
+        let reserved = lp_assets_after
+            .checked_add(fees_after)
+            .ok_or(TeraniumError::MathOverflow)?;
+        require!(post >= reserved, TeraniumError::InsufficientVaultLiquidity);
+
+        // User pays base into base vault; the vault must receive at least the amount priced above.
+        let balance_before = accounts.base_vault_token_account.amount;
+        transfer_checked(
+            &accounts.base_token_program,
+            user_from.to_account_info(),
+            &accounts.base_mint,
+            accounts.base_vault_token_account.to_account_info(),
+            accounts.user.to_account_info(),
+            remaining_accounts,
+            amount,
+            &[],
+        )?;
+        accounts.base_vault_token_account.reload()?;
+        let actual_received = accounts
+            .base_vault_token_account
+            .amount
+            .checked_sub(balance_before)
+            .ok_or(TeraniumError::MathOverflow)?;
+        require!(actual_received >= received, TeraniumError::TransferAmountMismatch);
+
+        // Vault pays quote tokens to user.
+        let quote_vault_key = accounts.quote_vault.key();
+        let quote_auth_seeds: &[&[u8]] = &[
+            b"vault_authority",
+            quote_vault_key.as_ref(),
+            &[accounts.quote_vault.authority_bump],
+        ];
+
+        transfer_checked(
+            &accounts.quote_token_program,
+            accounts.quote_vault_token_account.to_account_info(),
+            &accounts.quote_mint,
+            user_to.to_account_info(),
+            accounts.quote_vault_authority.to_account_info(),
+            remaining_accounts,
+            sent,
+            &[quote_auth_seeds],
+        )?;
+
+        accounts.quote_vault.total_deposits = lp_assets_after;
+        accounts.quote_vault.accrued_fees = fees_after;
+        accounts.base_vault.total_deposits = accounts
+            .base_vault
+            .total_deposits
+            .checked_add(actual_received)
+            .ok_or(TeraniumError::MathOverflow)?;
+
+        SwapDirection::BaseToQuote
+    } else {
+        // quote -> base
+        // The gross output is paid from LP assets; the fee moves from LP assets to accrued fees.
+        let lp_assets_after = accounts
+            .base_vault
+            .total_deposits
+            .checked_sub(gross_out)
+            .ok_or(TeraniumError::InsufficientVaultLiquidity)?;
+        let fees_after = accounts
+            .base_vault
+            .accrued_fees
+            .checked_add(fee)
+            .ok_or(TeraniumError::MathOverflow)?;
+
+        // Ensure base vault remains solvent against deposits and accrued fees after paying out.
+        let post = accounts
+            .base_vault_token_account
+            .amount
+            .checked_sub(sent)
+            .ok_or(TeraniumError::InsufficientVaultLiquidity)?;
+        let reserved = lp_assets_after
+            .checked_add(fees_after)
+            .ok_or(TeraniumError::MathOverflow)?;
+        require!(post >= reserved, TeraniumError::InsufficientVaultLiquidity);
+
+        // User pays quote tokens into quote vault; the vault must receive at least the amount priced above.
+        let balance_before = accounts.quote_vault_token_account.amount;
+        transfer_checked(
+            &accounts.quote_token_program,
+            user_from.to_account_info(),
+            &accounts.quote_mint,
+            accounts.quote_vault_token_account.to_account_info(),
+            accounts.user.to_account_info(),
+            remaining_accounts,
+            amount,
+            &[],
+        )?;
+        accounts.quote_vault_token_account.reload()?;
+        let actual_received = accounts
+            .quote_vault_token_account
+            .amount
+            .checked_sub(balance_before)
+            .ok_or(TeraniumError::MathOverflow)?;
+        require!(actual_received >= received, TeraniumError::TransferAmountMismatch);
+
+        // Vault pays base to user.
+        let base_vault_key = accounts.base_vault.key();
+        let base_auth_seeds: &[&[u8]] = &[
+            b"vault_authority",
+            base_vault_key.as_ref(),
+            &[accounts.base_vault.authority_bump],
+        ];
+
+        transfer_checked(
+            &accounts.base_token_program,
+            accounts.base_vault_token_account.to_account_info(),
+            &accounts.base_mint,
+            user_to.to_account_info(),
+            accounts.base_vault_authority.to_account_info(),
+            remaining_accounts,
+            sent,
+            &[base_auth_seeds],
+        )?;
+
+        accounts.base_vault.total_deposits = lp_assets_after;
+        accounts.base_vault.accrued_fees = fees_after;
+        accounts.quote_vault.total_deposits = accounts
+            .quote_vault
+            .total_deposits
+            .checked_add(actual_received)
+            .ok_or(TeraniumError::MathOverflow)?;
+
+        SwapDirection::QuoteToBase
+    };
+
+    emit!(OracleSwapped {
+        user: accounts.user.key(),
+        base_vault: accounts.base_vault.key(),
+        quote_vault: accounts.quote_vault.key(),
+        from_mint,
+        to_mint,
+        amount_in: amount,
+        amount_out,
+        oracle_price: price.price,
+        oracle_conf: price.conf,
+        oracle_expo: price.expo,
+        direction: direction as u8,
+        fee_amount: fee,
+        execution_price: i64::try_from(px).map_err(|_| TeraniumError::MathOverflow)?,
+        fallback_price: fallback,
+    });
+
+    Ok(())
+}
+
+/// The account a change applies to: the protocol config for the timelock, the vault for vault changes,
+/// the pair config otherwise.
+fn change_target(
+    change: &ParamChange,
+    protocol_config: &Account<ProtocolConfig>,
+    pair_config: &Option<Account<PairConfig>>,
+    vault: &Option<Account<VaultAccount>>,
+) -> Result<Pubkey> {
+    let target = match change {
+        ParamChange::Timelock { .. } => Some(protocol_config.key()),
+        ParamChange::VaultDepositCaps { .. } | ParamChange::VaultTransferHooks { .. } => {
+            vault.as_ref().map(|vault| vault.key())
+        }
+        _ => pair_config.as_ref().map(|pair| pair.key()),
+    };
+    target.ok_or(error!(TeraniumError::ChangeTargetMismatch))
+}
+
+/// Counts a queued change on its pair or vault target, which cannot be closed while the count is nonzero.
+fn track_pending_change(
+    change: &ParamChange,
+    pair_config: &mut Option<Account<PairConfig>>,
+    vault: &mut Option<Account<VaultAccount>>,
+    queued: bool,
+) -> Result<()> {
+    let count = match change {
+        ParamChange::Timelock { .. } => return Ok(()),
+        ParamChange::VaultDepositCaps { .. } | ParamChange::VaultTransferHooks { .. } => {
+            &mut vault.as_mut().ok_or(TeraniumError::ChangeTargetMismatch)?.pending_changes
+        }
+        _ => &mut pair_config.as_mut().ok_or(TeraniumError::ChangeTargetMismatch)?.pending_changes,
+    };
+    *count = if queued { count.checked_add(1) } else { count.checked_sub(1) }.ok_or(TeraniumError::MathOverflow)?;
+    Ok(())
+}
+
+/// Binds the oracle named by a change. Address-bound kinds must pass that exact account;
+/// `PythPull` names no account (`oracle == Pubkey::default()`) and takes any update for the feed.
+fn bind_change_oracle(change: ChangeOracle, price_account: Option<&AccountInfo>) -> Result<OracleBinding> {
+    let price_account = price_account.ok_or(TeraniumError::OracleAccountMismatch)?;
+    if change.kind == OracleKind::PythPull as u8 {
+        require_keys_eq!(change.oracle, Pubkey::default(), TeraniumError::OracleAccountMismatch);
+    } else {
+        require_keys_eq!(price_account.key(), change.oracle, TeraniumError::OracleAccountMismatch);
+    }
+
+    oracle::bind(
+        change.kind,
+        change.feed_id,
+        change.min_verification_signatures,
+        price_account,
+    )
+}
+
+/// Writes a validated pair change and emits its update event.
+fn apply_pair_change(
+    pair: &mut Account<PairConfig>,
+    change: &ParamChange,
+    price_account: Option<&AccountInfo>,
+) -> Result<()> {
+    match *change {
+        ParamChange::PairFee { fee_bps } => {
+            let previous_fee_bps = pair.fee_bps;
+            pair.fee_bps = fee_bps;
+
+            emit!(PairFeeUpdated {
+                pair_config: pair.key(),
+                previous_fee_bps,
+                fee_bps,
+            });
+        }
+        ParamChange::PairPricing {
+            pricing_mode,
+            conf_multiplier_bps,
+        } => {
+            pair.pricing_mode = pricing_mode;
+            pair.conf_multiplier_bps = conf_multiplier_bps;
+
+            emit!(PairPricingUpdated {
+                pair_config: pair.key(),
+                pricing_mode,
+                conf_multiplier_bps,
+            });
+        }
+        ParamChange::PairOracle { oracle } => {
+            let binding = bind_change_oracle(oracle, price_account)?;
+            pair.set_primary_oracle(&binding);
+
+            emit!(PairOracleUpdated {
+                pair_config: pair.key(),
+                oracle_kind: pair.oracle_kind,
+                oracle: pair.oracle,
+                oracle_owner: pair.oracle_owner,
+                feed_id: pair.feed_id,
+                min_verification_signatures: pair.min_verification_signatures,
+            });
+        }
+        ParamChange::PairSecondaryOracle {
+            oracle,
+            max_deviation_bps,
+            dual_oracle_pricing,
+        } => {
+            let binding = bind_change_oracle(oracle, price_account)?;
+            pair.secondary_oracle = binding;
+            pair.max_oracle_deviation_bps = max_deviation_bps;
+            pair.dual_oracle_pricing = dual_oracle_pricing;
+
+            emit!(PairSecondaryOracleUpdated {
+                pair_config: pair.key(),
+                oracle_kind: binding.kind,
+                oracle: binding.oracle,
+                oracle_owner: binding.owner,
+                feed_id: binding.feed_id,
+                min_verification_signatures: binding.min_verification_signatures,
+                max_deviation_bps,
+                dual_oracle_pricing,
+            });
+        }
+        ParamChange::ClearPairSecondaryOracle => {
+            pair.secondary_oracle = OracleBinding::default();
+            pair.max_oracle_deviation_bps = 0;
+            pair.dual_oracle_pricing = DualOraclePricing::Conservative as u8;
+
+            emit!(PairSecondaryOracleUpdated {
+                pair_config: pair.key(),
+                oracle_kind: 0,
+                oracle: Pubkey::default(),
+                oracle_owner: Pubkey::default(),
+                feed_id: [0; 32],
+                min_verification_signatures: 0,
+                max_deviation_bps: 0,
+                dual_oracle_pricing: 0,
+            });
+        }
+        ParamChange::PairStaleness {
+            max_staleness_seconds,
+            max_staleness_slots,
+        } => {
+            pair.max_staleness_seconds = max_staleness_seconds;
+            pair.max_staleness_slots = max_staleness_slots;
+
+            emit!(PairStalenessUpdated {
+                pair_config: pair.key(),
+                max_staleness_seconds,
+                max_staleness_slots,
+            });
+        }
+        ParamChange::PairEmaGuard { max_ema_deviation_bps } => {
+            pair.max_ema_deviation_bps = max_ema_deviation_bps;
+
+            emit!(PairEmaGuardUpdated {
+                pair_config: pair.key(),
+                max_ema_deviation_bps,
+            });
+        }
+        ParamChange::PairVolumeCaps {
+            max_trade_notional,
+            max_window_notional,
+            volume_window_seconds,
+        } => {
+            pair.max_trade_notional = max_trade_notional;
+            pair.max_window_notional = max_window_notional;
+            pair.volume_window_seconds = volume_window_seconds;
+            pair.volume_bucket = 0;
+            pair.base_to_quote_volume = [0; VOLUME_BUCKETS];
+            pair.quote_to_base_volume = [0; VOLUME_BUCKETS];
+
+            emit!(PairVolumeCapsUpdated {
+                pair_config: pair.key(),
+                max_trade_notional,
+                max_window_notional,
+                volume_window_seconds,
+            });
+        }
+        ParamChange::PairCircuitBreaker {
+            max_price_jump_bps,
+            circuit_breaker_window_seconds,
+        } => {
+            pair.max_price_jump_bps = max_price_jump_bps;
+            pair.circuit_breaker_window_seconds = circuit_breaker_window_seconds;
+
+            emit!(PairCircuitBreakerUpdated {
+                pair_config: pair.key(),
+                max_price_jump_bps,
+                circuit_breaker_window_seconds,
+            });
+        }
+        ParamChange::PairFallbackBounds {
+            max_fallback_deviation_bps,
+            max_fallback_trade_notional,
+        } => {
+            pair.max_fallback_deviation_bps = max_fallback_deviation_bps;
+            pair.max_fallback_trade_notional = max_fallback_trade_notional;
+            if max_fallback_deviation_bps == 0 {
+                pair.fallback_expires_at = 0;
+            }
+
+            emit!(PairFallbackBoundsUpdated {
+                pair_config: pair.key(),
+                max_fallback_deviation_bps,
+                max_fallback_trade_notional,
+            });
+        }
+        ParamChange::VaultDepositCaps { .. }
+        | ParamChange::VaultTransferHooks { .. }
+        | ParamChange::Timelock { .. } => {
+            return err!(TeraniumError::ChangeTargetMismatch);
+        }
+    }
+
+    Ok(())
+}
+
+/// Adds `notional` to the pair's rolling volume for one direction; fails with
+/// `VolumeCapExceeded` when the window total would go above `max_window_notional`.
+///
+/// The window is `VOLUME_BUCKETS` buckets of `volume_window_seconds / VOLUME_BUCKETS` each;
+/// buckets that fell out of the window are cleared before the check.
+fn record_volume(pair: &mut PairConfig, now: i64, from_base: bool, notional: u64) -> Result<()> {
+    if pair.max_window_notional == 0 {
+        return Ok(());
+    }
+
+    let bucket_seconds = (pair.volume_window_seconds / VOLUME_BUCKETS as u32) as i64;
+    let bucket = now.div_euclid(bucket_seconds);
+    let elapsed = bucket.saturating_sub(pair.volume_bucket);
+    if elapsed > 0 {
+        for i in 1..=elapsed.min(VOLUME_BUCKETS as i64) {
+            let idx = (pair.volume_bucket + i).rem_euclid(VOLUME_BUCKETS as i64) as usize;
+            pair.base_to_quote_volume[idx] = 0;
+            pair.quote_to_base_volume[idx] = 0;
+        }
+        pair.volume_bucket = bucket;
+    }
+
+    let max_window_notional = pair.max_window_notional;
+    let volume = if from_base {
+        &mut pair.base_to_quote_volume
+    } else {
+        &mut pair.quote_to_base_volume
+    };
+    let window_total = volume
+        .iter()
+        .try_fold(0u64, |acc, v| acc.checked_add(*v))
+        .ok_or(TeraniumError::MathOverflow)?;
+    require!(
+        window_total.checked_add(notional).ok_or(TeraniumError::MathOverflow)? <= max_window_notional,
+        TeraniumError::VolumeCapExceeded
+    );
+
+    let idx = pair.volume_bucket.rem_euclid(VOLUME_BUCKETS as i64) as usize;
+    volume[idx] = volume[idx].checked_add(notional).ok_or(TeraniumError::MathOverflow)?;
+
+    Ok(())
+}
+
+fn pow10_u128(exp: u32) -> Result<u128> {
+    // Bound to keep computation safe and deterministic.
+    require!(exp <= 38, TeraniumError::MathOverflow);
+    let mut v: u128 = 1;
+    for _ in 0..exp {
+        v = v.checked_mul(10).ok_or(TeraniumError::MathOverflow)?;
+    }
+    Ok(v)
+}
+
+fn mul_div_u64(a: u64, b: u64, denom: u64, round_up: bool) -> Result<u64> {
+    require!(denom > 0, TeraniumError::MathOverflow);
+    let num = (a as u128).checked_mul(b as u128).ok_or(TeraniumError::MathOverflow)?;
+    let mut q = num / denom as u128;
+    if round_up && num % denom as u128 != 0 {
+        q += 1;
+    }
+    u64::try_from(q).map_err(|_| TeraniumError::MathOverflow.into())
+}
+
+/// Reallocates a program-owned account to `new_len`, zero-filling the tail and topping up rent from `payer`.
+fn grow_account<'info>(
+    account: &AccountInfo<'info>,
+    payer: &Signer<'info>,
+    system_program: &Program<'info, System>,
+    new_len: usize,
+) -> Result<()> {
+    let required = Rent::get()?.minimum_balance(new_len);
+    let shortfall = required.saturating_sub(account.lamports());
+    if shortfall > 0 {
+        system_program::transfer(
+            CpiContext::new(
+                system_program.to_account_info(),
+                system_program::Transfer {
+                    from: payer.to_account_info(),
+                    to: account.clone(),
+                },
+            ),
+            shortfall,
+        )?;
+    }
+    account.realloc(new_len, true)?;
+    Ok(())
+}
+
+/// The mint's Token-2022 transfer fee on sending `amount` this epoch; 0 without the extension.
+fn transfer_fee(mint: &InterfaceAccount<Mint>, amount: u64, epoch: u64) -> Result<u64> {
+    match transfer_fee_config(mint)? {
+        Some(config) => Ok(config
+            .calculate_epoch_fee(epoch, amount)
+            .ok_or(TeraniumError::MathOverflow)?),
+        None => Ok(0),
+    }
+}
+
+/// The fee to add to `post_fee_amount` so that the recipient gets at least `post_fee_amount`.
+fn transfer_fee_inverse(mint: &InterfaceAccount<Mint>, post_fee_amount: u64, epoch: u64) -> Result<u64> {
+    match transfer_fee_config(mint)? {
+        Some(config) => Ok(config
+            .calculate_inverse_epoch_fee(epoch, post_fee_amount)
+            .ok_or(TeraniumError::MathOverflow)?),
+        None => Ok(0),
+    }
+}
+
+/// `transfer_checked` that appends the extra accounts a Token-2022 transfer hook needs, resolved from
+/// `remaining_accounts` by the hook's validation account. Plain SPL Token transfers ignore them.
+#[allow(clippy::too_many_arguments)]
+fn transfer_checked<'info>(
+    token_program: &Interface<'info, TokenInterface>,
+    from: AccountInfo<'info>,
+    mint: &InterfaceAccount<'info, Mint>,
+    to: AccountInfo<'info>,
+    authority: AccountInfo<'info>,
+    remaining_accounts: &[AccountInfo<'info>],
+    amount: u64,
+    signer_seeds: &[&[&[u8]]],
+) -> Result<()> {
+    spl_token_2022::onchain::invoke_transfer_checked(
+        token_program.key,
+        from,
+        mint.to_account_info(),
+        to,
+        authority,
+        remaining_accounts,
+        amount,
+        mint.decimals,
+        signer_seeds,
+    )?;
+    Ok(())
+}
+
+/// Inflows are only accepted while the mint's transfer hook program (if any) is on the vault's allowlist,
+/// so funds never enter a vault whose outflows depend on an unvetted hook. Outflows are never gated: a hook
+/// runs de-escalated and can only fail a transfer, so gating them would only freeze withdrawals.
+fn check_transfer_hook(vault: &VaultAccount, mint: &InterfaceAccount<Mint>) -> Result<()> {
+    let info = mint.to_account_info();
+    if *info.owner != spl_token_2022::ID {
+        return Ok(());
+    }
+    let data = info.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    if let Some(program_id) = transfer_hook::get_program_id(&state) {
+        require!(
+            vault.transfer_hook_programs.contains(&program_id),
+            TeraniumError::TransferHookNotAllowed
+        );
+    }
+    Ok(())
+}
+
+/// Screens a Token-2022 mint before a vault is created for it; see `ALLOWED_MINT_EXTENSIONS`.
+fn check_mint_extensions(mint: &InterfaceAccount<Mint>) -> Result<()> {
+    let info = mint.to_account_info();
+    if *info.owner != spl_token_2022::ID {
+        return Ok(());
+    }
+    require!(mint.freeze_authority.is_none(), TeraniumError::UnsupportedMint);
+
+    let data = info.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    for extension in state.get_extension_types()? {
+        require!(ALLOWED_MINT_EXTENSIONS.contains(&extension), TeraniumError::UnsupportedMint);
+    }
+    if let Ok(default_state) = state.get_extension::<DefaultAccountState>() {
+        require!(default_state.state != AccountState::Frozen as u8, TeraniumError::UnsupportedMint);
+    }
+    Ok(())
+}
+
+fn transfer_fee_config(mint: &InterfaceAccount<Mint>) -> Result<Option<TransferFeeConfig>> {
+    let info = mint.to_account_info();
+    if *info.owner != spl_token_2022::ID {
+        return Ok(None);
+    }
+    let data = info.try_borrow_data()?;
+    let state = StateWithExtensions::<spl_token_2022::state::Mint>::unpack(&data)?;
+    Ok(state.get_extension::<TransferFeeConfig>().ok().copied())
+}
+
+fn swap_fee(amount_out: u64, fee_bps: u16) -> Result<u64> {
+    // fee = amount_out * fee_bps / 10_000 (rounded down; fee_bps <= MAX_FEE_BPS keeps fee < amount_out)
+    let fee = (amount_out as u128)
+        .checked_mul(fee_bps as u128)
+        .ok_or(TeraniumError::MathOverflow)?
+        / BPS_DENOM as u128;
+    Ok(fee as u64)
+}
+
+/// Price the vault trades at, in the oracle exponent.
+///
+/// In `ConfidenceAdjusted` mode the price moves against the trader by `k * conf`, rounded in the vault's favor.
+fn execution_price(
+    price: u128,
+    conf: u128,
+    pricing_mode: u8,
+    conf_multiplier_bps: u16,
+    vault_buys_base: bool,
+) -> Result<u128> {
+    if pricing_mode == PricingMode::Mid as u8 {
+        return Ok(price);
+    }
+    require!(pricing_mode == PricingMode::ConfidenceAdjusted as u8, TeraniumError::InvalidPricingMode);
+
+    let spread = conf
+        .checked_mul(conf_multiplier_bps as u128)
+        .ok_or(TeraniumError::MathOverflow)?;
+    let spread = div_u128(spread, BPS_DENOM as u128, true)?;
+
+    if vault_buys_base {
+        let bid = price.checked_sub(spread).ok_or(TeraniumError::OracleInvalidPrice)?;
+        require!(bid > 0, TeraniumError::OracleInvalidPrice);
+        Ok(bid)
+    } else {
+        price.checked_add(spread).ok_or(TeraniumError::MathOverflow.into())
+    }
+}
+
+/// Smallest gross output whose exact-in payout (`gross - swap_fee(gross)`) covers `amount_out`.
+fn gross_out_for_net(amount_out: u64, fee_bps: u16) -> Result<u64> {
+    // gross - floor(gross * fee_bps / 10_000) = ceil(gross * net_bps / 10_000) >= amount_out
+    //   <=> gross * net_bps > (amount_out - 1) * 10_000, so gross = floor((amount_out - 1) * 10_000 / net_bps) + 1
+    let net_bps = (BPS_DENOM as u64)
+        .checked_sub(fee_bps as u64)
+        .ok_or(TeraniumError::InvalidFeeBps)?;
+    if amount_out == 0 {
+        return Ok(0);
+    }
+    mul_div_u64(amount_out - 1, BPS_DENOM as u64, net_bps, false)?
+        .checked_add(1)
+        .ok_or(TeraniumError::MathOverflow.into())
+}
+
+fn div_u128(num: u128, denom: u128, round_up: bool) -> Result<u128> {
+    let q = num.checked_div(denom).ok_or(TeraniumError::SwapZeroOut)?;
+    let rem = num - q * denom;
+    if round_up && rem > 0 {
+        return q.checked_add(1).ok_or(TeraniumError::MathOverflow.into());
+    }
+    Ok(q)
+}
+
+fn base_to_quote(
+    amount_base: u128,
+    price: u128,
+    expo: i32,
+    base_decimals: u32,
+    quote_decimals: u32,
+    round_up: bool,
+) -> Result<u128> {
+    // quote_out = amount_base * price * 10^{quote_decimals} * 10^{max(expo,0)} / (10^{base_decimals} * 10^{max(-expo,0)})
+    let expo_pos: u32 = if expo > 0 { expo as u32 } else { 0 };
+    let expo_neg: u32 = if expo < 0 { (-expo) as u32 } else { 0 };
+
+    let num = amount_base
+        .checked_mul(price)
+        .ok_or(TeraniumError::MathOverflow)?
+        .checked_mul(pow10_u128(quote_decimals)?)
+        .ok_or(TeraniumError::MathOverflow)?
+        .checked_mul(pow10_u128(expo_pos)?)
+        .ok_or(TeraniumError::MathOverflow)?;
+
+    let denom = pow10_u128(base_decimals)?
+        .checked_mul(pow10_u128(expo_neg)?)
+        .ok_or(TeraniumError::MathOverflow)?;
+
+    div_u128(num, denom, round_up)
+}
+
+fn quote_to_base(
+    amount_quote: u128,
+    price: u128,
+    expo: i32,
+    base_decimals: u32,
+    quote_decimals: u32,
+    round_up: bool,
+) -> Result<u128> {
+    // base_out = amount_quote * 10^{base_decimals} * 10^{max(-expo,0)} / (price * 10^{quote_decimals} * 10^{max(expo,0)})
+    let expo_pos: u32 = if expo > 0 { expo as u32 } else { 0 };
+    let expo_neg: u32 = if expo < 0 { (-expo) as u32 } else { 0 };
+
+    let num = amount_quote
+        .checked_mul(pow10_u128(base_decimals)?)
+        .ok_or(TeraniumError::MathOverflow)?
+        .checked_mul(pow10_u128(expo_neg)?)
+        .ok_or(TeraniumError::MathOverflow)?;
+
+    let denom = price
+        .checked_mul(pow10_u128(quote_decimals)?)
+        .ok_or(TeraniumError::MathOverflow)?
+        .checked_mul(pow10_u128(expo_pos)?)
+        .ok_or(TeraniumError::MathOverflow)?;
+
+    div_u128(num, denom, round_up)
+}
+
+#[derive(Accounts)]
+pub struct MigrateVault<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    /// CHECK: legacy layout cannot be deserialized; owner checked here, discriminator checked in handler.
+    #[account(mut, owner = crate::ID @ TeraniumError::InvalidMigrationAccount)]
+    pub vault: UncheckedAccount<'info>,
+
+    /// Vault ATA; pinned in the handler once the vault's mint and authority bump are readable.
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct MigratePosition<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    #[account(
+        seeds = [b"vault", vault.mint.as_ref()],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, VaultAccount>,
+
+    /// CHECK: legacy layout cannot be deserialized; owner checked here, discriminator and vault link checked in handler.
+    #[account(mut, owner = crate::ID @ TeraniumError::InvalidMigrationAccount)]
+    pub user_position: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+#[instruction(mint: Pubkey)]
+pub struct InitializeVault<'info> {
+    #[account(mut)]
+    pub payer: Signer<'info>,
+
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init,
+        payer = payer,
+        space = 8 + VaultAccount::INIT_SPACE,
+        seeds = [b"vault", mint.key().as_ref()],
+        bump
+    )]
+    pub vault: Account<'info, VaultAccount>,
+
+    /// CHECK: PDA signer only; validated by seeds.
+    #[account(
+        seeds = [b"vault_authority", vault.key().as_ref()],
+        bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    #[account(
+        init,
+        payer = payer,
+        associated_token::mint = mint,
+        associated_token::authority = vault_authority,
+        associated_token::token_program = token_program
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub associated_token_program: Program<'info, AssociatedToken>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct InitializeProtocol<'info> {
+    #[account(mut)]
+    pub authority: Signer<'info>,
+
+    #[account(constraint = program.programdata_address()? == Some(program_data.key()) @ TeraniumError::Unauthorized)]
+    pub program: Program<'info, crate::program::Teranium>,
+
+    #[account(constraint = program_data.upgrade_authority_address == Some(authority.key()) @ TeraniumError::Unauthorized)]
+    pub program_data: Account<'info, ProgramData>,
+
+    #[account(
+        init,
+        payer = authority,
+        space = 8 + ProtocolConfig::INIT_SPACE,
+        seeds = [b"protocol_config"],
+        bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeAdmin<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+        has_one = admin @ TeraniumError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct AcceptAdmin<'info> {
+    pub pending_admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetGuardian<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+        has_one = admin @ TeraniumError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetTreasury<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+        has_one = admin @ TeraniumError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+}
+
+#[derive(Accounts)]
+pub struct SetPause<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", vault.mint.as_ref()],
+        bump = vault.bump
+    )]
+    pub vault: Option<Account<'info, VaultAccount>>,
+}
+
+#[derive(Accounts)]
+pub struct InitializePair<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+        has_one = admin @ TeraniumError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", base_vault.mint.as_ref()],
+        bump = base_vault.bump
+    )]
+    pub base_vault: Account<'info, VaultAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", quote_vault.mint.as_ref()],
+        bump = quote_vault.bump
+    )]
+    pub quote_vault: Account<'info, VaultAccount>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + PairConfig::INIT_SPACE,
+        seeds = [b"pair_config", base_vault.key().as_ref(), quote_vault.key().as_ref()],
+        bump
+    )]
+    pub pair_config: Account<'info, PairConfig>,
+
+    /// CHECK: must be owned by the Pyth oracle program and parse as a Pyth price feed.
+    pub pyth_price_account: UncheckedAccount<'info>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct SetPairConfig<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+        has_one = admin @ TeraniumError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"pair_config", pair_config.base_vault.as_ref(), pair_config.quote_vault.as_ref()],
+        bump = pair_config.bump
+    )]
+    pub pair_config: Account<'info, PairConfig>,
+}
+
+#[derive(Accounts)]
+pub struct ProposeChange<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+        has_one = admin @ TeraniumError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        init,
+        payer = admin,
+        space = 8 + PendingChange::INIT_SPACE,
+        seeds = [b"pending_change", protocol_config.next_change_id.to_le_bytes().as_ref()],
+        bump
+    )]
+    pub pending_change: Account<'info, PendingChange>,
+
+    #[account(
+        mut,
+        seeds = [b"pair_config", pair_config.base_vault.as_ref(), pair_config.quote_vault.as_ref()],
+        bump = pair_config.bump
+    )]
+    pub pair_config: Option<Account<'info, PairConfig>>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", vault.mint.as_ref()],
+        bump = vault.bump
+    )]
+    pub vault: Option<Account<'info, VaultAccount>>,
+
+    /// CHECK: oracle changes only; validated by owner check and parsing under the proposed binding.
+    pub price_account: Option<UncheckedAccount<'info>>,
+
+    pub system_program: Program<'info, System>,
+}
+
+#[derive(Accounts)]
+pub struct ExecuteChange<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+        has_one = admin @ TeraniumError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        close = proposer,
+        seeds = [b"pending_change", pending_change.id.to_le_bytes().as_ref()],
+        bump = pending_change.bump
+    )]
+    pub pending_change: Account<'info, PendingChange>,
+
+    /// CHECK: receives the pending change's rent; bound to its proposer.
+    #[account(mut, address = pending_change.proposer @ TeraniumError::Unauthorized)]
+    pub proposer: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"pair_config", pair_config.base_vault.as_ref(), pair_config.quote_vault.as_ref()],
+        bump = pair_config.bump
+    )]
+    pub pair_config: Option<Account<'info, PairConfig>>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", vault.mint.as_ref()],
+        bump = vault.bump
+    )]
+    pub vault: Option<Account<'info, VaultAccount>>,
+
+    /// CHECK: oracle changes only; validated by owner check and parsing under the new binding.
+    pub price_account: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct CancelChange<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        close = proposer,
+        seeds = [b"pending_change", pending_change.id.to_le_bytes().as_ref()],
+        bump = pending_change.bump
+    )]
+    pub pending_change: Account<'info, PendingChange>,
+
+    /// CHECK: receives the pending change's rent; bound to its proposer.
+    #[account(mut, address = pending_change.proposer @ TeraniumError::Unauthorized)]
+    pub proposer: UncheckedAccount<'info>,
+
+    #[account(
+        mut,
+        seeds = [b"pair_config", pair_config.base_vault.as_ref(), pair_config.quote_vault.as_ref()],
+        bump = pair_config.bump
+    )]
+    pub pair_config: Option<Account<'info, PairConfig>>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", vault.mint.as_ref()],
+        bump = vault.bump
+    )]
+    pub vault: Option<Account<'info, VaultAccount>>,
+}
+
+#[derive(Accounts)]
+pub struct TripCircuitBreaker<'info> {
+    /// Admin or guardian tripping the breaker by hand; omitted for the permissionless price check.
+    pub authority: Option<Signer<'info>>,
+
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"pair_config", pair_config.base_vault.as_ref(), pair_config.quote_vault.as_ref()],
+        bump = pair_config.bump
+    )]
+    pub pair_config: Account<'info, PairConfig>,
+
+    /// CHECK: owner bound by the pair config; address (legacy) or feed id and verification level (pull)
+    /// checked when the price is loaded.
+    #[account(owner = pair_config.oracle_owner @ TeraniumError::OracleOwnerMismatch)]
+    pub pyth_price_account: UncheckedAccount<'info>,
+
+    /// CHECK: required when the pair has a secondary oracle; owner and binding checked when the price is loaded.
+    pub secondary_price_account: Option<UncheckedAccount<'info>>,
+}
+
+#[derive(Accounts)]
+pub struct ResetCircuitBreaker<'info> {
+    pub authority: Signer<'info>,
+
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"pair_config", pair_config.base_vault.as_ref(), pair_config.quote_vault.as_ref()],
+        bump = pair_config.bump
+    )]
+    pub pair_config: Account<'info, PairConfig>,
+}
+
+#[derive(Accounts)]
+pub struct CollectFees<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+        has_one = admin @ TeraniumError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", vault.mint.as_ref()],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, VaultAccount>,
+
+    /// CHECK: PDA signer only; validated by seeds.
+    #[account(
+        seeds = [b"vault_authority", vault.key().as_ref()],
+        bump = vault.authority_bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    #[account(
+        address = vault.mint @ TeraniumError::MintMismatch,
+        mint::token_program = token_program
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        constraint = vault_token_account.owner == vault_authority.key() @ TeraniumError::Unauthorized
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = treasury_token_account.owner == protocol_config.treasury @ TeraniumError::Unauthorized
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct DecommissionVault<'info> {
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+        has_one = admin @ TeraniumError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", vault.mint.as_ref()],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, VaultAccount>,
+}
+
+#[derive(Accounts)]
+pub struct CloseVault<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+        has_one = admin @ TeraniumError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        close = admin,
+        seeds = [b"vault", vault.mint.as_ref()],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, VaultAccount>,
+
+    /// CHECK: PDA signer only; validated by seeds.
+    #[account(
+        seeds = [b"vault_authority", vault.key().as_ref()],
+        bump = vault.authority_bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    #[account(
+        address = vault.mint @ TeraniumError::MintMismatch,
+        mint::token_program = token_program
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        associated_token::mint = mint,
+        associated_token::authority = vault_authority,
+        associated_token::token_program = token_program
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = treasury_token_account.owner == protocol_config.treasury @ TeraniumError::Unauthorized
+    )]
+    pub treasury_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ClosePair<'info> {
+    #[account(mut)]
+    pub admin: Signer<'info>,
+
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump,
+        has_one = admin @ TeraniumError::Unauthorized
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        close = admin,
+        seeds = [b"pair_config", base_vault.key().as_ref(), quote_vault.key().as_ref()],
+        bump = pair_config.bump
+    )]
+    pub pair_config: Account<'info, PairConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", base_vault.mint.as_ref()],
+        bump = base_vault.bump
+    )]
+    pub base_vault: Account<'info, VaultAccount>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", quote_vault.mint.as_ref()],
+        bump = quote_vault.bump
+    )]
+    pub quote_vault: Account<'info, VaultAccount>,
+}
+
+#[derive(Accounts)]
+pub struct Deposit<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", vault.mint.as_ref()],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, VaultAccount>,
+
+    /// CHECK: PDA signer only; validated by seeds.
+    #[account(
+        seeds = [b"vault_authority", vault.key().as_ref()],
+        bump = vault.authority_bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    #[account(
+        address = vault.mint @ TeraniumError::MintMismatch,
+        mint::token_program = token_program
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        init_if_needed,
+        payer = owner,
+        space = 8 + UserPosition::INIT_SPACE,
+        seeds = [b"user_position", vault.key().as_ref(), owner.key().as_ref()],
+        bump
+    )]
+    pub user_position: Account<'info, UserPosition>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.owner == owner.key() @ TeraniumError::Unauthorized
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = vault_token_account.owner == vault_authority.key() @ TeraniumError::Unauthorized
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+    pub rent: Sysvar<'info, Rent>,
+}
+
+#[derive(Accounts)]
+pub struct Withdraw<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
+    #[account(
+        mut,
+        seeds = [b"vault", vault.mint.as_ref()],
+        bump = vault.bump
+    )]
+    pub vault: Account<'info, VaultAccount>,
+
+    /// CHECK: PDA signer only; validated by seeds.
+    #[account(
+        seeds = [b"vault_authority", vault.key().as_ref()],
+        bump = vault.authority_bump
+    )]
+    pub vault_authority: UncheckedAccount<'info>,
+
+    #[account(
+        address = vault.mint @ TeraniumError::MintMismatch,
+        mint::token_program = token_program
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
+    #[account(
+        mut,
+        seeds = [b"user_position", vault.key().as_ref(), owner.key().as_ref()],
+        bump,
+        constraint = user_position.owner == owner.key() @ TeraniumError::Unauthorized,
+        constraint = user_position.vault == vault.key() @ TeraniumError::InvalidUserPosition
+    )]
+    pub user_position: Account<'info, UserPosition>,
+
+    #[account(
+        mut,
+        constraint = user_token_account.owner == owner.key() @ TeraniumError::Unauthorized
+    )]
+    pub user_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    #[account(
+        mut,
+        constraint = vault_token_account.owner == vault_authority.key() @ TeraniumError::Unauthorized
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct DepositSol<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
+
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
 
     #[account(
         mut,
@@ -445,26 +3092,26 @@ pub struct Deposit<'info> {
 
     #[account(
         mut,
-        constraint = user_token_account.owner == owner.key() @ TeraniumError::Unauthorized
-    )]
-    pub user_token_account: Account<'info, TokenAccount>,
-
-    #[account(
-        mut,
-        constraint = vault_token_account.owner == vault_authority.key() @ TeraniumError::Unauthorized
+        constraint = vault_token_account.owner == vault_authority.key() @ TeraniumError::Unauthorized,
+        token::token_program = token_program
     )]
-    pub vault_token_account: Account<'info, TokenAccount>,
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
 
     pub system_program: Program<'info, System>,
-    pub token_program: Program<'info, Token>,
-    pub rent: Sysvar<'info, Rent>,
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
 #[derive(Accounts)]
-pub struct Withdraw<'info> {
+pub struct WithdrawSol<'info> {
     #[account(mut)]
     pub owner: Signer<'info>,
 
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
     #[account(
         mut,
         seeds = [b"vault", vault.mint.as_ref()],
@@ -479,6 +3126,12 @@ pub struct Withdraw<'info> {
     )]
     pub vault_authority: UncheckedAccount<'info>,
 
+    #[account(
+        address = vault.mint @ TeraniumError::MintMismatch,
+        mint::token_program = token_program
+    )]
+    pub mint: InterfaceAccount<'info, Mint>,
+
     #[account(
         mut,
         seeds = [b"user_position", vault.key().as_ref(), owner.key().as_ref()],
@@ -490,17 +3143,68 @@ pub struct Withdraw<'info> {
 
     #[account(
         mut,
-        constraint = user_token_account.owner == owner.key() @ TeraniumError::Unauthorized
+        constraint = vault_token_account.owner == vault_authority.key() @ TeraniumError::Unauthorized
+    )]
+    pub vault_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    /// Temporary native account, closed to the owner within the instruction.
+    #[account(
+        init,
+        payer = owner,
+        seeds = [b"unwrap", vault.key().as_ref(), owner.key().as_ref()],
+        bump,
+        token::mint = mint,
+        token::authority = vault_authority,
+        token::token_program = token_program
     )]
-    pub user_token_account: Account<'info, TokenAccount>,
+    pub unwrap_token_account: InterfaceAccount<'info, TokenAccount>,
+
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
+}
+
+#[derive(Accounts)]
+pub struct ClosePosition<'info> {
+    #[account(mut)]
+    pub owner: Signer<'info>,
 
     #[account(
         mut,
-        constraint = vault_token_account.owner == vault_authority.key() @ TeraniumError::Unauthorized
+        seeds = [b"user_position", user_position.vault.as_ref(), owner.key().as_ref()],
+        bump,
+        constraint = user_position.owner == owner.key() @ TeraniumError::Unauthorized
+    )]
+    pub user_position: Account<'info, UserPosition>,
+
+    /// Required only while the position holds (dust) shares; may no longer exist otherwise.
+    #[account(mut, address = user_position.vault @ TeraniumError::InvalidUserPosition)]
+    pub vault: Option<Account<'info, VaultAccount>>,
+}
+
+#[derive(Accounts)]
+pub struct OracleSwapSol<'info> {
+    pub swap: OracleSwap<'info>,
+
+    #[account(
+        address = anchor_spl::token::spl_token::native_mint::ID @ TeraniumError::NotNativeMint,
+        mint::token_program = token_program
+    )]
+    pub native_mint: InterfaceAccount<'info, Mint>,
+
+    /// Temporary native account standing in for the user's native side, closed to the user within the instruction.
+    #[account(
+        init,
+        payer = swap.user,
+        seeds = [b"wrap", swap.user.key().as_ref()],
+        bump,
+        token::mint = native_mint,
+        token::authority = swap.user,
+        token::token_program = token_program
     )]
-    pub vault_token_account: Account<'info, TokenAccount>,
+    pub wrap_token_account: InterfaceAccount<'info, TokenAccount>,
 
-    pub token_program: Program<'info, Token>,
+    pub system_program: Program<'info, System>,
+    pub token_program: Interface<'info, TokenInterface>,
 }
 
 #[derive(Accounts)]
@@ -508,6 +3212,12 @@ pub struct OracleSwap<'info> {
     #[account(mut)]
     pub user: Signer<'info>,
 
+    #[account(
+        seeds = [b"protocol_config"],
+        bump = protocol_config.bump
+    )]
+    pub protocol_config: Account<'info, ProtocolConfig>,
+
     #[account(
         mut,
         seeds = [b"vault", base_vault.mint.as_ref()],
@@ -526,48 +3236,84 @@ pub struct OracleSwap<'info> {
         mut,
         constraint = base_vault_token_account.owner == base_vault_authority.key() @ TeraniumError::Unauthorized
     )]
-    pub base_vault_token_account: Account<'info, TokenAccount>,
+    pub base_vault_token_account: InterfaceAccount<'info, TokenAccount>,
 
-    pub base_mint: Account<'info, Mint>,
+    #[account(mint::token_program = base_token_program)]
+    pub base_mint: InterfaceAccount<'info, Mint>,
 
     #[account(
         mut,
-        seeds = [b"vault", usdc_vault.mint.as_ref()],
-        bump = usdc_vault.bump
+        seeds = [b"vault", quote_vault.mint.as_ref()],
+        bump = quote_vault.bump
     )]
-    pub usdc_vault: Account<'info, VaultAccount>,
+    pub quote_vault: Account<'info, VaultAccount>,
 
     /// CHECK: PDA signer only; validated by seeds.
     #[account(
-        seeds = [b"vault_authority", usdc_vault.key().as_ref()],
-        bump = usdc_vault.authority_bump
+        seeds = [b"vault_authority", quote_vault.key().as_ref()],
+        bump = quote_vault.authority_bump
     )]
-    pub usdc_vault_authority: UncheckedAccount<'info>,
+    pub quote_vault_authority: UncheckedAccount<'info>,
 
     #[account(
         mut,
-        constraint = usdc_vault_token_account.owner == usdc_vault_authority.key() @ TeraniumError::Unauthorized
+        constraint = quote_vault_token_account.owner == quote_vault_authority.key() @ TeraniumError::Unauthorized
     )]
-    pub usdc_vault_token_account: Account<'info, TokenAccount>,
+    pub quote_vault_token_account: InterfaceAccount<'info, TokenAccount>,
 
-    pub usdc_mint: Account<'info, Mint>,
+    #[account(mint::token_program = quote_token_program)]
+    pub quote_mint: InterfaceAccount<'info, Mint>,
 
+    /// Omitted only on the native side of `oracle_swap_sol`.
     #[account(
         mut,
         constraint = user_from_token_account.owner == user.key() @ TeraniumError::Unauthorized
     )]
-    pub user_from_token_account: Account<'info, TokenAccount>,
+    pub user_from_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
 
+    /// Omitted only on the native side of `oracle_swap_sol`.
     #[account(
         mut,
         constraint = user_to_token_account.owner == user.key() @ TeraniumError::Unauthorized
     )]
-    pub user_to_token_account: Account<'info, TokenAccount>,
+    pub user_to_token_account: Option<InterfaceAccount<'info, TokenAccount>>,
+
+    #[account(
+        mut,
+        seeds = [b"pair_config", base_vault.key().as_ref(), quote_vault.key().as_ref()],
+        bump = pair_config.bump
+    )]
+    pub pair_config: Account<'info, PairConfig>,
 
-    /// CHECK: validated by parsing Pyth price feed header.
+    /// CHECK: owner bound by the pair config; address (legacy) or feed id and verification level (pull)
+    /// checked when the price is loaded.
+    #[account(owner = pair_config.oracle_owner @ TeraniumError::OracleOwnerMismatch)]
     pub pyth_price_account: UncheckedAccount<'info>,
 
-    pub token_program: Program<'info, Token>,
+    /// CHECK: required when the pair has a secondary oracle; owner and binding checked when the price is loaded.
+    pub secondary_price_account: Option<UncheckedAccount<'info>>,
+
+    pub base_token_program: Interface<'info, TokenInterface>,
+    pub quote_token_program: Interface<'info, TokenInterface>,
+}
+
+#[account]
+pub struct ProtocolConfig {
+    pub admin: Pubkey,
+    pub pending_admin: Pubkey,
+    pub version: u8,
+    pub bump: u8,
+    pub guardian: Pubkey,
+    pub pause_flags: u8,
+    pub treasury: Pubkey,
+    /// Delay between `propose_change` and `execute_change`.
+    pub timelock_seconds: u32,
+    /// Id (and PDA seed) of the next `PendingChange`.
+    pub next_change_id: u64,
+}
+
+impl Space for ProtocolConfig {
+    const INIT_SPACE: usize = 32 + 32 + 1 + 1 + 32 + 1 + 32 + 4 + 8;
 }
 
 #[account]
@@ -576,21 +3322,399 @@ pub struct VaultAccount {
     pub bump: u8,
     pub authority_bump: u8,
     pub total_deposits: u64,
+    pub pause_flags: u8,
+    pub accrued_fees: u64,
+    pub total_shares: u64,
+    /// Cap on `total_principal`; 0 = unlimited.
+    pub max_total_deposits: u64,
+    /// Cap on each position's `deposited`; 0 = unlimited.
+    pub max_deposit_per_user: u64,
+    /// Transfer hook programs the vault accepts inflows under; default entries are unused.
+    pub transfer_hook_programs: [Pubkey; MAX_TRANSFER_HOOK_PROGRAMS],
+    /// Withdraw-only mode set by `decommission_vault`; never cleared.
+    pub decommissioned: bool,
+    /// Open pairs using the vault as base or quote.
+    pub pair_count: u32,
+    /// Queued changes targeting the vault.
+    pub pending_changes: u32,
+    /// Sum of positions' principal (`UserPosition::deposited`), excluding swap PnL; `max_total_deposits` caps it.
+    pub total_principal: u64,
 }
 
 impl Space for VaultAccount {
-    const INIT_SPACE: usize = 32 + 1 + 1 + 8;
+    const INIT_SPACE: usize = 32 + 1 + 1 + 8 + 1 + 8 + 8 + 8 + 8 + 32 * MAX_TRANSFER_HOOK_PROGRAMS + 1 + 4 + 4
+        + 8;
 }
 
 #[account]
 pub struct UserPosition {
     pub owner: Pubkey,
     pub vault: Pubkey,
+    /// Principal basis; reduced pro rata to shares burned on withdraw.
     pub deposited: u64,
+    pub shares: u64,
 }
 
 impl Space for UserPosition {
-    const INIT_SPACE: usize = 32 + 32 + 8;
+    const INIT_SPACE: usize = 32 + 32 + 8 + 8;
+}
+
+#[account]
+pub struct PairConfig {
+    pub base_vault: Pubkey,
+    pub quote_vault: Pubkey,
+    pub oracle: Pubkey,
+    pub oracle_owner: Pubkey,
+    pub bump: u8,
+    pub fee_bps: u16,
+    pub pricing_mode: u8,
+    pub conf_multiplier_bps: u16,
+    /// `OracleKind` of the primary oracle.
+    pub oracle_kind: u8,
+    /// Pyth feed id expected in `PriceUpdateV2` accounts (pull only).
+    pub feed_id: [u8; 32],
+    /// Minimum guardian signatures for partially verified updates; 0 requires full verification (pull only).
+    pub min_verification_signatures: u8,
+    /// Cross-checked against the primary on every swap when set (non-default owner).
+    pub secondary_oracle: OracleBinding,
+    pub max_oracle_deviation_bps: u16,
+    /// `DualOraclePricing`; only used with a secondary oracle.
+    pub dual_oracle_pricing: u8,
+    /// Max cluster-time age of the oracle price; 0 disables the check.
+    pub max_staleness_seconds: u32,
+    /// Max slot age of the oracle price; 0 disables the check.
+    pub max_staleness_slots: u64,
+    /// Mint of `quote_vault`; the asset the base is priced in.
+    pub quote_mint: Pubkey,
+    /// Max spot-vs-EMA divergence in bps; 0 disables the guard.
+    pub max_ema_deviation_bps: u16,
+    /// Max quote-leg notional of a single swap; 0 = unlimited.
+    pub max_trade_notional: u64,
+    /// Max quote-leg notional per direction within the rolling window; 0 = unlimited.
+    pub max_window_notional: u64,
+    pub volume_window_seconds: u32,
+    /// Index (`unix_timestamp / bucket_seconds`) of the most recent volume bucket.
+    pub volume_bucket: i64,
+    pub base_to_quote_volume: [u64; VOLUME_BUCKETS],
+    pub quote_to_base_volume: [u64; VOLUME_BUCKETS],
+    /// Oracle price (and exponent) of the last executed swap; the circuit breaker's reference.
+    pub last_price: i64,
+    pub last_price_expo: i32,
+    /// Cluster time of the last executed swap; 0 = no reference.
+    pub last_price_timestamp: i64,
+    /// Max move from `last_price` in bps before the pair is paused; 0 disables the breaker.
+    pub max_price_jump_bps: u16,
+    pub circuit_breaker_window_seconds: u32,
+    /// Set by a price jump (in a swap or `trip_circuit_breaker`) or by the admin or guardian; swaps fail until
+    /// the admin or guardian resets it.
+    pub circuit_breaker_tripped: bool,
+    /// Max distance of a fallback price from `last_price` in bps; 0 disables fallback prices.
+    pub max_fallback_deviation_bps: u16,
+    /// Per-swap cap (quote units) while a fallback price is active.
+    pub max_fallback_trade_notional: u64,
+    /// Admin fallback price, used instead of the oracle until `fallback_expires_at`.
+    pub fallback_price: i64,
+    pub fallback_conf: u64,
+    pub fallback_expo: i32,
+    pub fallback_expires_at: i64,
+    /// Queued changes targeting the pair.
+    pub pending_changes: u32,
+}
+
+impl Space for PairConfig {
+    const INIT_SPACE: usize =
+        32 + 32 + 32 + 32 + 1 + 2 + 1 + 2 + 1 + 32 + 1 + OracleBinding::INIT_SPACE + 2 + 1 + 4 + 8 + 32 + 2 + 8 + 8 + 4 + 8 + 8 * VOLUME_BUCKETS * 2
+        + 8 + 4 + 8 + 2 + 4 + 1
+        + 2 + 8 + 8 + 8 + 4 + 8
+        + 4;
+}
+
+impl PairConfig {
+    fn primary_oracle(&self) -> OracleBinding {
+        OracleBinding {
+            kind: self.oracle_kind,
+            oracle: self.oracle,
+            owner: self.oracle_owner,
+            feed_id: self.feed_id,
+            min_verification_signatures: self.min_verification_signatures,
+        }
+    }
+
+    fn set_primary_oracle(&mut self, binding: &OracleBinding) {
+        self.oracle_kind = binding.kind;
+        self.oracle = binding.oracle;
+        self.oracle_owner = binding.owner;
+        self.feed_id = binding.feed_id;
+        self.min_verification_signatures = binding.min_verification_signatures;
+    }
+}
+
+#[account]
+pub struct PendingChange {
+    pub id: u64,
+    pub bump: u8,
+    /// Admin that proposed the change; receives the rent back when it is executed or cancelled.
+    pub proposer: Pubkey,
+    /// Pair config, vault or protocol config the change applies to.
+    pub target: Pubkey,
+    pub proposed_at: i64,
+    /// Earliest execution time.
+    pub eta: i64,
+    pub change: ParamChange,
+}
+
+impl Space for PendingChange {
+    const INIT_SPACE: usize = 8 + 1 + 32 + 32 + 8 + 8 + ParamChange::INIT_SPACE;
+}
+
+/// Oracle source named by a `ParamChange` (see `bind_change_oracle`).
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub struct ChangeOracle {
+    /// `OracleKind`.
+    pub kind: u8,
+    /// Account to bind for address-bound kinds; default for Pyth pull.
+    pub oracle: Pubkey,
+    /// Pyth pull only.
+    pub feed_id: [u8; 32],
+    /// Pyth pull only; 0 requires full verification.
+    pub min_verification_signatures: u8,
+}
+
+/// A timelocked risk-parameter change, queued by `propose_change`.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone)]
+pub enum ParamChange {
+    /// Swap fee on `amount_out`, <= 1_000 bps.
+    PairFee { fee_bps: u16 },
+    /// `PricingMode` and the confidence multiplier k (<= 50_000 bps).
+    PairPricing { pricing_mode: u8, conf_multiplier_bps: u16 },
+    /// Rebinds the primary oracle.
+    PairOracle { oracle: ChangeOracle },
+    /// Binds a secondary oracle; swaps fail when the feeds diverge by more than `max_deviation_bps` (1..=10_000)
+    /// and otherwise price at the `DualOraclePricing` side.
+    PairSecondaryOracle {
+        oracle: ChangeOracle,
+        max_deviation_bps: u16,
+        dual_oracle_pricing: u8,
+    },
+    ClearPairSecondaryOracle,
+    /// Max oracle age (<= 3_600 seconds, <= 9_000 slots; 0 disables a check, one must stay enabled).
+    PairStaleness {
+        max_staleness_seconds: u32,
+        max_staleness_slots: u64,
+    },
+    /// Max spot-vs-EMA divergence (<= 10_000 bps; 0 disables).
+    PairEmaGuard { max_ema_deviation_bps: u16 },
+    /// Per-swap and rolling-window volume caps; resets the accumulator.
+    PairVolumeCaps {
+        max_trade_notional: u64,
+        max_window_notional: u64,
+        volume_window_seconds: u32,
+    },
+    /// Circuit breaker threshold and window (0 bps disables).
+    PairCircuitBreaker {
+        max_price_jump_bps: u16,
+        circuit_breaker_window_seconds: u32,
+    },
+    /// Fallback price bounds (both 0 disables fallback prices and ends an active one).
+    PairFallbackBounds {
+        max_fallback_deviation_bps: u16,
+        max_fallback_trade_notional: u64,
+    },
+    /// Vault deposit caps (0 = unlimited).
+    VaultDepositCaps {
+        max_total_deposits: u64,
+        max_deposit_per_user: u64,
+    },
+    /// Replaces the vault's transfer hook allowlist (default entries are unused).
+    VaultTransferHooks {
+        programs: [Pubkey; MAX_TRANSFER_HOOK_PROGRAMS],
+    },
+    /// Delay between proposing and executing changes (3_600..=1_209_600 seconds).
+    Timelock { timelock_seconds: u32 },
+}
+
+impl ParamChange {
+    /// Largest variant (`VaultTransferHooks`) plus the variant tag.
+    const INIT_SPACE: usize = 1 + 32 * MAX_TRANSFER_HOOK_PROGRAMS;
+
+    /// Argument checks that do not depend on the target account.
+    fn validate(&self) -> Result<()> {
+        match *self {
+            ParamChange::PairFee { fee_bps } => {
+                require!(fee_bps <= MAX_FEE_BPS, TeraniumError::InvalidFeeBps);
+            }
+            ParamChange::PairPricing {
+                pricing_mode,
+                conf_multiplier_bps,
+            } => {
+                require!(
+                    pricing_mode == PricingMode::Mid as u8 || pricing_mode == PricingMode::ConfidenceAdjusted as u8,
+                    TeraniumError::InvalidPricingMode
+                );
+                require!(conf_multiplier_bps <= MAX_CONF_MULTIPLIER_BPS, TeraniumError::InvalidPricingMode);
+            }
+            ParamChange::PairSecondaryOracle {
+                max_deviation_bps,
+                dual_oracle_pricing,
+                ..
+            } => {
+                require!(
+                    max_deviation_bps > 0 && max_deviation_bps as i128 <= BPS_DENOM,
+                    TeraniumError::InvalidDeviationBps
+                );
+                require!(
+                    dual_oracle_pricing == DualOraclePricing::Conservative as u8
+                        || dual_oracle_pricing == DualOraclePricing::Median as u8,
+                    TeraniumError::InvalidPricingMode
+                );
+            }
+            ParamChange::PairStaleness {
+                max_staleness_seconds,
+                max_staleness_slots,
+            } => {
+                require!(
+                    max_staleness_seconds <= MAX_STALENESS_SECONDS && max_staleness_slots <= MAX_STALENESS_SLOTS,
+                    TeraniumError::InvalidStaleness
+                );
+                require!(
+                    max_staleness_seconds > 0 || max_staleness_slots > 0,
+                    TeraniumError::InvalidStaleness
+                );
+            }
+            ParamChange::PairEmaGuard { max_ema_deviation_bps } => {
+                require!(max_ema_deviation_bps as i128 <= BPS_DENOM, TeraniumError::InvalidDeviationBps);
+            }
+            ParamChange::PairVolumeCaps {
+                max_window_notional,
+                volume_window_seconds,
+                ..
+            } => {
+                if max_window_notional > 0 {
+                    require!(
+                        volume_window_seconds >= VOLUME_BUCKETS as u32
+                            && volume_window_seconds <= MAX_VOLUME_WINDOW_SECONDS,
+                        TeraniumError::InvalidVolumeCaps
+                    );
+                }
+            }
+            ParamChange::PairCircuitBreaker {
+                max_price_jump_bps,
+                circuit_breaker_window_seconds,
+            } => {
+                if max_price_jump_bps > 0 {
+                    require!(
+                        max_price_jump_bps as i128 <= BPS_DENOM
+                            && circuit_breaker_window_seconds > 0
+                            && circuit_breaker_window_seconds <= MAX_CIRCUIT_BREAKER_WINDOW_SECONDS,
+                        TeraniumError::InvalidCircuitBreaker
+                    );
+                }
+            }
+            ParamChange::PairFallbackBounds {
+                max_fallback_deviation_bps,
+                max_fallback_trade_notional,
+            } => {
+                let disabled = max_fallback_deviation_bps == 0 && max_fallback_trade_notional == 0;
+                require!(
+                    disabled
+                        || (max_fallback_deviation_bps > 0
+                            && max_fallback_deviation_bps as i128 <= BPS_DENOM
+                            && max_fallback_trade_notional > 0),
+                    TeraniumError::InvalidFallbackBounds
+                );
+            }
+            ParamChange::Timelock { timelock_seconds } => {
+                require!(
+                    (MIN_TIMELOCK_SECONDS..=MAX_TIMELOCK_SECONDS).contains(&timelock_seconds),
+                    TeraniumError::InvalidTimelock
+                );
+            }
+            ParamChange::PairOracle { .. }
+            | ParamChange::ClearPairSecondaryOracle
+            | ParamChange::VaultDepositCaps { .. }
+            | ParamChange::VaultTransferHooks { .. } => {}
+        }
+
+        Ok(())
+    }
+
+    fn oracle_binding(&self) -> Option<ChangeOracle> {
+        match *self {
+            ParamChange::PairOracle { oracle } | ParamChange::PairSecondaryOracle { oracle, .. } => Some(oracle),
+            _ => None,
+        }
+    }
+}
+
+impl Space for ChangeOracle {
+    const INIT_SPACE: usize = 1 + 32 + 32 + 1;
+}
+
+/// Which oracle prices a pair and how it is bound (see `ParamChange::PairOracle`).
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy, Default)]
+pub struct OracleBinding {
+    /// `OracleKind`.
+    pub kind: u8,
+    /// Bound account for address-bound sources; default for Pyth pull.
+    pub oracle: Pubkey,
+    /// Program that must own the oracle account (derived from `kind`); default when unset.
+    pub owner: Pubkey,
+    pub feed_id: [u8; 32],
+    pub min_verification_signatures: u8,
+}
+
+impl Space for OracleBinding {
+    const INIT_SPACE: usize = 1 + 32 + 32 + 32 + 1;
+}
+
+impl OracleBinding {
+    fn is_set(&self) -> bool {
+        self.owner != Pubkey::default()
+    }
+}
+
+#[event]
+pub struct ProtocolInitialized {
+    pub protocol_config: Pubkey,
+    pub admin: Pubkey,
+    pub version: u8,
+}
+
+#[event]
+pub struct AdminTransferProposed {
+    pub protocol_config: Pubkey,
+    pub admin: Pubkey,
+    pub pending_admin: Pubkey,
+}
+
+#[event]
+pub struct AdminTransferAccepted {
+    pub protocol_config: Pubkey,
+    pub previous_admin: Pubkey,
+    pub admin: Pubkey,
+}
+
+#[event]
+pub struct GuardianUpdated {
+    pub protocol_config: Pubkey,
+    pub previous_guardian: Pubkey,
+    pub guardian: Pubkey,
+}
+
+#[event]
+pub struct PauseUpdated {
+    pub authority: Pubkey,
+    /// Protocol config for global flags, or the vault for per-vault flags.
+    pub target: Pubkey,
+    pub previous_flags: u8,
+    pub pause_flags: u8,
+}
+
+#[event]
+pub struct TreasuryUpdated {
+    pub protocol_config: Pubkey,
+    pub previous_treasury: Pubkey,
+    pub treasury: Pubkey,
 }
 
 #[event]
@@ -601,6 +3725,172 @@ pub struct VaultInitialized {
     pub authority_bump: u8,
 }
 
+#[event]
+pub struct VaultDepositCapsUpdated {
+    pub vault: Pubkey,
+    pub max_total_deposits: u64,
+    pub max_deposit_per_user: u64,
+}
+
+#[event]
+pub struct VaultTransferHooksUpdated {
+    pub vault: Pubkey,
+    pub programs: [Pubkey; MAX_TRANSFER_HOOK_PROGRAMS],
+}
+
+#[event]
+pub struct PairInitialized {
+    pub pair_config: Pubkey,
+    pub base_vault: Pubkey,
+    pub quote_vault: Pubkey,
+    pub oracle: Pubkey,
+    pub oracle_owner: Pubkey,
+    pub quote_mint: Pubkey,
+}
+
+#[event]
+pub struct PairFeeUpdated {
+    pub pair_config: Pubkey,
+    pub previous_fee_bps: u16,
+    pub fee_bps: u16,
+}
+
+#[event]
+pub struct PairPricingUpdated {
+    pub pair_config: Pubkey,
+    pub pricing_mode: u8,
+    pub conf_multiplier_bps: u16,
+}
+
+#[event]
+pub struct PairOracleUpdated {
+    pub pair_config: Pubkey,
+    pub oracle_kind: u8,
+    pub oracle: Pubkey,
+    pub oracle_owner: Pubkey,
+    pub feed_id: [u8; 32],
+    pub min_verification_signatures: u8,
+}
+
+#[event]
+pub struct PairSecondaryOracleUpdated {
+    pub pair_config: Pubkey,
+    pub oracle_kind: u8,
+    pub oracle: Pubkey,
+    pub oracle_owner: Pubkey,
+    pub feed_id: [u8; 32],
+    pub min_verification_signatures: u8,
+    pub max_deviation_bps: u16,
+    pub dual_oracle_pricing: u8,
+}
+
+#[event]
+pub struct PairStalenessUpdated {
+    pub pair_config: Pubkey,
+    pub max_staleness_seconds: u32,
+    pub max_staleness_slots: u64,
+}
+
+#[event]
+pub struct PairEmaGuardUpdated {
+    pub pair_config: Pubkey,
+    pub max_ema_deviation_bps: u16,
+}
+
+#[event]
+pub struct PairVolumeCapsUpdated {
+    pub pair_config: Pubkey,
+    pub max_trade_notional: u64,
+    pub max_window_notional: u64,
+    pub volume_window_seconds: u32,
+}
+
+#[event]
+pub struct PairCircuitBreakerUpdated {
+    pub pair_config: Pubkey,
+    pub max_price_jump_bps: u16,
+    pub circuit_breaker_window_seconds: u32,
+}
+
+#[event]
+pub struct CircuitBreakerTripped {
+    pub pair_config: Pubkey,
+    pub last_price: i64,
+    pub last_price_timestamp: i64,
+    /// Reading that jumped; 0 when tripped by hand.
+    pub price: i64,
+    pub expo: i32,
+    pub timestamp: i64,
+    /// Admin or guardian that tripped the breaker by hand; default when a price jump tripped it.
+    pub authority: Pubkey,
+}
+
+#[event]
+pub struct CircuitBreakerReset {
+    pub pair_config: Pubkey,
+    pub authority: Pubkey,
+}
+
+#[event]
+pub struct PairFallbackBoundsUpdated {
+    pub pair_config: Pubkey,
+    pub max_fallback_deviation_bps: u16,
+    pub max_fallback_trade_notional: u64,
+}
+
+#[event]
+pub struct PairFallbackPriceSet {
+    pub pair_config: Pubkey,
+    pub price: i64,
+    pub conf: u64,
+    pub expo: i32,
+    pub expires_at: i64,
+    pub reference_price: i64,
+}
+
+#[event]
+pub struct PairFallbackPriceCleared {
+    pub pair_config: Pubkey,
+}
+
+#[event]
+pub struct ChangeProposed {
+    pub pending_change: Pubkey,
+    pub id: u64,
+    pub target: Pubkey,
+    pub eta: i64,
+    pub change: ParamChange,
+}
+
+#[event]
+pub struct ChangeExecuted {
+    pub pending_change: Pubkey,
+    pub id: u64,
+    pub target: Pubkey,
+}
+
+#[event]
+pub struct ChangeCancelled {
+    pub pending_change: Pubkey,
+    pub id: u64,
+    pub target: Pubkey,
+    pub authority: Pubkey,
+}
+
+#[event]
+pub struct TimelockUpdated {
+    pub protocol_config: Pubkey,
+    pub previous_timelock_seconds: u32,
+    pub timelock_seconds: u32,
+}
+
+#[event]
+pub struct FeesCollected {
+    pub vault: Pubkey,
+    pub treasury_token_account: Pubkey,
+    pub amount: u64,
+}
+
 #[event]
 pub struct Deposited {
     pub owner: Pubkey,
@@ -608,28 +3898,123 @@ pub struct Deposited {
     pub amount: u64,
     pub deposited_after: u64,
     pub total_deposits_after: u64,
+    pub shares: u64,
+    pub shares_after: u64,
+    pub total_shares_after: u64,
+}
+
+#[event]
+pub struct Withdrawn {
+    pub owner: Pubkey,
+    pub vault: Pubkey,
+    pub amount: u64,
+    pub deposited_after: u64,
+    pub total_deposits_after: u64,
+    pub shares: u64,
+    pub shares_after: u64,
+    pub total_shares_after: u64,
+}
+
+#[event]
+pub struct VaultDecommissioned {
+    pub vault: Pubkey,
+    pub total_deposits: u64,
+    pub total_shares: u64,
+}
+
+#[event]
+pub struct VaultClosed {
+    pub vault: Pubkey,
+    pub mint: Pubkey,
+    pub treasury_token_account: Pubkey,
+    pub swept: u64,
+}
+
+#[event]
+pub struct PositionClosed {
+    pub owner: Pubkey,
+    pub vault: Pubkey,
+    pub user_position: Pubkey,
+}
+
+#[event]
+pub struct PositionDustForfeited {
+    pub owner: Pubkey,
+    pub vault: Pubkey,
+    pub shares: u64,
+    pub principal: u64,
+    pub total_shares_after: u64,
+}
+
+#[event]
+pub struct OrphanedAssetsReclaimed {
+    pub vault: Pubkey,
+    pub amount: u64,
+    pub accrued_fees_after: u64,
+}
+
+#[event]
+pub struct PairClosed {
+    pub pair_config: Pubkey,
+    pub base_vault: Pubkey,
+    pub quote_vault: Pubkey,
+}
+
+#[event]
+pub struct VaultMigrated {
+    pub vault: Pubkey,
+    pub total_deposits: u64,
+    pub total_shares: u64,
+}
+
+#[event]
+pub struct PositionMigrated {
+    pub user_position: Pubkey,
+    pub owner: Pubkey,
+    pub vault: Pubkey,
+    pub shares: u64,
+}
+
+/// Last moment a swap may execute, in cluster time or slots.
+#[derive(AnchorSerialize, AnchorDeserialize, Clone, Copy)]
+pub enum SwapDeadline {
+    UnixTimestamp { unix_timestamp: i64 },
+    Slot { slot: u64 },
+}
+
+#[repr(u8)]
+pub enum SwapDirection {
+    BaseToQuote = 0,
+    QuoteToBase = 1,
+}
+
+#[repr(u8)]
+pub enum PricingMode {
+    Mid = 0,
+    ConfidenceAdjusted = 1,
 }
 
-#[event]
-pub struct Withdrawn {
-    pub owner: Pubkey,
-    pub vault: Pubkey,
-    pub amount: u64,
-    pub deposited_after: u64,
-    pub total_deposits_after: u64,
+#[repr(u8)]
+pub enum DualOraclePricing {
+    Conservative = 0,
+    Median = 1,
 }
 
 #[repr(u8)]
-pub enum SwapDirection {
-    BaseToUsdc = 0,
-    UsdcToBase = 1,
+pub enum OracleKind {
+    PythLegacy = 0,
+    PythPull = 1,
+    SwitchboardOnDemand = 2,
+    /// Program-owned `MockPrice` account; only available in `localnet` builds.
+    #[cfg(feature = "localnet")]
+    Mock = 3,
 }
 
 #[event]
 pub struct OracleSwapped {
     pub user: Pubkey,
     pub base_vault: Pubkey,
-    pub usdc_vault: Pubkey,
+    pub quote_vault: Pubkey,
     pub from_mint: Pubkey,
     pub to_mint: Pubkey,
     pub amount_in: u64,
@@ -638,6 +4023,10 @@ pub struct OracleSwapped {
     pub oracle_conf: u64,
     pub oracle_expo: i32,
     pub direction: u8,
+    pub fee_amount: u64,
+    pub execution_price: i64,
+    /// True when the swap used the admin fallback price instead of the oracle.
+    pub fallback_price: bool,
 }
 
 #[error_code]
@@ -678,8 +4067,8 @@ pub enum TeraniumError {
     #[msg("Oracle slippage exceeded")]
     OracleSlippageExceeded,
 
-    #[msg("Invalid USDC mint")]
-    InvalidUsdcMint,
+    #[msg("Invalid quote mint")]
+    InvalidQuoteMint,
 
     #[msg("Invalid swap pair")]
     InvalidSwapPair,
@@ -689,4 +4078,1012 @@ pub enum TeraniumError {
 
     #[msg("Insufficient vault liquidity")]
     InsufficientVaultLiquidity,
+
+    #[msg("Oracle account is not a valid price feed")]
+    OracleInvalidAccount,
+
+    #[msg("Oracle account does not match pair config")]
+    OracleAccountMismatch,
+
+    #[msg("Oracle account owner does not match pair config")]
+    OracleOwnerMismatch,
+
+    #[msg("Invalid admin")]
+    InvalidAdmin,
+
+    #[msg("No pending admin transfer")]
+    NoPendingAdmin,
+
+    #[msg("Invalid pause flags")]
+    InvalidPauseFlags,
+
+    #[msg("Deposits are paused")]
+    PausedDeposits,
+
+    #[msg("Withdrawals are paused")]
+    PausedWithdrawals,
+
+    #[msg("Swaps are paused")]
+    PausedSwaps,
+
+    #[msg("Invalid fee bps")]
+    InvalidFeeBps,
+
+    #[msg("Invalid treasury")]
+    InvalidTreasury,
+
+    #[msg("No fees to collect")]
+    NoFeesToCollect,
+
+    #[msg("Amount converts to zero shares")]
+    ZeroShares,
+
+    #[msg("Vault has outstanding shares but no assets")]
+    VaultDepleted,
+
+    #[msg("Account is already migrated")]
+    AlreadyMigrated,
+
+    #[msg("Account cannot be migrated")]
+    InvalidMigrationAccount,
+
+    #[msg("Swap output is below the minimum amount out")]
+    SlippageToleranceExceeded,
+
+    #[msg("Invalid pricing mode")]
+    InvalidPricingMode,
+
+    #[msg("Invalid oracle kind")]
+    InvalidOracleKind,
+
+    #[msg("Oracle feed id does not match pair config")]
+    OracleFeedMismatch,
+
+    #[msg("Oracle update is not sufficiently verified")]
+    OracleVerificationInsufficient,
+
+    #[msg("Primary and secondary oracle prices deviate beyond the pair threshold")]
+    OracleDeviation,
+
+    #[msg("Invalid oracle deviation bps")]
+    InvalidDeviationBps,
+
+    #[msg("Invalid oracle staleness limits")]
+    InvalidStaleness,
+
+    #[msg("Oracle spot price deviates too far from its EMA")]
+    OracleEmaDeviation,
+
+    #[msg("Oracle does not publish an EMA price")]
+    OracleEmaUnavailable,
+
+    #[msg("Swap deadline has passed")]
+    SwapExpired,
+
+    #[msg("Swap exceeds the pair's max trade size")]
+    TradeSizeExceeded,
+
+    #[msg("Swap exceeds the pair's rolling volume cap")]
+    VolumeCapExceeded,
+
+    #[msg("Invalid volume caps")]
+    InvalidVolumeCaps,
+
+    #[msg("Deposit exceeds the vault or per-user cap; compared values are the amount and the remaining headroom")]
+    DepositCapExceeded,
+
+    #[msg("Pair is paused by the circuit breaker")]
+    CircuitBreakerTripped,
+
+    #[msg("Invalid circuit breaker settings")]
+    InvalidCircuitBreaker,
+
+    #[msg("Invalid fallback price bounds")]
+    InvalidFallbackBounds,
+
+    #[msg("Fallback prices are disabled for this pair")]
+    FallbackPriceDisabled,
+
+    #[msg("Invalid fallback price")]
+    InvalidFallbackPrice,
+
+    #[msg("No oracle price with a matching exponent to bound the fallback price")]
+    FallbackReferenceUnavailable,
+
+    #[msg("Fallback price deviates too far from the last oracle price")]
+    FallbackPriceDeviation,
+
+    #[msg("Timelock has not elapsed")]
+    TimelockNotElapsed,
+
+    #[msg("Pending change has expired")]
+    ChangeExpired,
+
+    #[msg("Change target account missing or mismatched")]
+    ChangeTargetMismatch,
+
+    #[msg("Invalid timelock")]
+    InvalidTimelock,
+
+    #[msg("Vault received less than the transfer was priced at")]
+    TransferAmountMismatch,
+
+    #[msg("Mint transfer hook program is not on the vault allowlist")]
+    TransferHookNotAllowed,
+
+    #[msg("Token account is not a native SOL account")]
+    NotNativeMint,
+
+    #[msg("User position still holds shares")]
+    PositionNotEmpty,
+
+    #[msg("Vault is decommissioned (withdraw-only)")]
+    VaultDecommissioned,
+
+    #[msg("Vault must be decommissioned first")]
+    VaultNotDecommissioned,
+
+    #[msg("Vault still holds deposits")]
+    VaultNotEmpty,
+
+    #[msg("Oracle price is within the circuit breaker threshold")]
+    CircuitBreakerNotTriggered,
+
+    #[msg("Vault still has open pairs or queued changes")]
+    VaultInUse,
+
+    #[msg("Pair has queued changes")]
+    PairInUse,
+
+    #[msg("Token-2022 mint has a freeze authority or an unsupported extension")]
+    UnsupportedMint,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use anchor_lang::error::ComparedValues;
+    use anchor_lang::solana_program::program_pack::Pack;
+    use anchor_spl::token_2022::spl_token_2022::extension::{
+        non_transferable::NonTransferable, permanent_delegate::PermanentDelegate, BaseStateWithExtensionsMut,
+        StateWithExtensionsMut,
+    };
+
+    fn protocol_config() -> ProtocolConfig {
+        ProtocolConfig {
+            admin: Pubkey::new_unique(),
+            pending_admin: Pubkey::default(),
+            version: 1,
+            bump: 255,
+            guardian: Pubkey::new_unique(),
+            pause_flags: 0,
+            treasury: Pubkey::new_unique(),
+            timelock_seconds: 0,
+            next_change_id: 0,
+        }
+    }
+
+    /// A program-owned account holding `value`; the backing memory is leaked so the `Account` can outlive the helper.
+    fn program_account<T: AccountSerialize + AccountDeserialize + Owner + Clone>(value: &T) -> Account<'static, T> {
+        let mut data = Vec::new();
+        value.try_serialize(&mut data).unwrap();
+
+        let info = AccountInfo::new(
+            Box::leak(Box::new(Pubkey::new_unique())),
+            false,
+            true,
+            Box::leak(Box::new(0)),
+            Box::leak(data.into_boxed_slice()),
+            &ID,
+            false,
+            0,
+        );
+        Account::try_from(Box::leak(Box::new(info))).unwrap()
+    }
+
+    fn vault_account(total_deposits: u64, total_shares: u64, total_principal: u64) -> Account<'static, VaultAccount> {
+        let vault = VaultAccount {
+            mint: Pubkey::new_unique(),
+            bump: 255,
+            authority_bump: 255,
+            total_deposits,
+            pause_flags: 0,
+            accrued_fees: 0,
+            total_shares,
+            max_total_deposits: 0,
+            max_deposit_per_user: 0,
+            transfer_hook_programs: [Pubkey::default(); MAX_TRANSFER_HOOK_PROGRAMS],
+            decommissioned: false,
+            pair_count: 0,
+            pending_changes: 0,
+            total_principal,
+        };
+        program_account(&vault)
+    }
+
+    /// A pair with every field zeroed: no oracle, limits, breaker or fallback configured.
+    fn pair_account() -> Account<'static, PairConfig> {
+        let data = vec![0u8; 8 + PairConfig::INIT_SPACE];
+        program_account(&PairConfig::try_deserialize_unchecked(&mut &data[..]).unwrap())
+    }
+
+    fn oracle_price(price: i64, expo: i32) -> oracle::OraclePrice {
+        oracle::OraclePrice {
+            price,
+            conf: 0,
+            expo,
+            publish_time: 0,
+            publish_slot: 0,
+            ema_price: None,
+        }
+    }
+
+    fn position(owner: Pubkey, vault: &Account<VaultAccount>, deposited: u64, shares: u64) -> UserPosition {
+        UserPosition {
+            owner,
+            vault: vault.key(),
+            deposited,
+            shares,
+        }
+    }
+
+    /// A freshly created position, as `init_if_needed` hands it to `prepare_deposit`.
+    fn unclaimed_position() -> UserPosition {
+        UserPosition {
+            owner: Pubkey::default(),
+            vault: Pubkey::default(),
+            deposited: 0,
+            shares: 0,
+        }
+    }
+
+    #[test]
+    fn mul_div_u64_rounds_as_requested() {
+        assert_eq!(mul_div_u64(10, 3, 4, false).unwrap(), 7);
+        assert_eq!(mul_div_u64(10, 3, 4, true).unwrap(), 8);
+        assert_eq!(mul_div_u64(10, 4, 4, true).unwrap(), 10);
+        assert_eq!(mul_div_u64(u64::MAX, u64::MAX, u64::MAX, false).unwrap(), u64::MAX);
+        assert_eq!(mul_div_u64(1, 1, 0, false).unwrap_err(), error!(TeraniumError::MathOverflow));
+        assert_eq!(mul_div_u64(u64::MAX, 2, 1, false).unwrap_err(), error!(TeraniumError::MathOverflow));
+    }
+
+    #[test]
+    fn deposits_mint_shares_at_the_exchange_rate() {
+        let owner = Pubkey::new_unique();
+        let mut vault = vault_account(0, 0, 0);
+        let mut first = unclaimed_position();
+        prepare_deposit(&protocol_config(), &vault, &mut first, owner, 1_000).unwrap();
+        credit_deposit(&mut vault, &mut first, owner, 1_000).unwrap();
+        assert_eq!((first.deposited, first.shares), (1_000, 1_000));
+
+        // Swap PnL raises the exchange rate to 1.5 assets per share.
+        vault.total_deposits += 500;
+
+        let other = Pubkey::new_unique();
+        let mut second = unclaimed_position();
+        prepare_deposit(&protocol_config(), &vault, &mut second, other, 301).unwrap();
+        credit_deposit(&mut vault, &mut second, other, 301).unwrap();
+        assert_eq!((second.deposited, second.shares), (301, 200));
+        assert_eq!(vault.total_deposits, 1_801);
+        assert_eq!(vault.total_shares, 1_200);
+        assert_eq!(vault.total_principal, 1_301);
+
+        assert_eq!(
+            credit_deposit(&mut vault, &mut second, other, 1).unwrap_err(),
+            error!(TeraniumError::ZeroShares)
+        );
+    }
+
+    #[test]
+    fn deposits_into_a_vault_without_shares_reclaim_orphaned_assets() {
+        let owner = Pubkey::new_unique();
+        let mut vault = vault_account(50, 0, 0);
+        vault.accrued_fees = 7;
+        let mut position = unclaimed_position();
+        prepare_deposit(&protocol_config(), &vault, &mut position, owner, 100).unwrap();
+        credit_deposit(&mut vault, &mut position, owner, 100).unwrap();
+
+        assert_eq!(vault.accrued_fees, 57);
+        assert_eq!(vault.total_deposits, 100);
+        assert_eq!(vault.total_shares, 100);
+        assert_eq!(position.shares, 100);
+    }
+
+    #[test]
+    fn withdrawals_burn_shares_rounded_up_and_principal_pro_rata() {
+        let owner = Pubkey::new_unique();
+        let mut vault = vault_account(1_500, 1_000, 1_000);
+        let mut position = position(owner, &vault, 1_000, 1_000);
+
+        let (shares, principal) = prepare_withdraw(&protocol_config(), &vault, &position, owner, 100).unwrap();
+        assert_eq!((shares, principal), (67, 67));
+        debit_withdraw(&mut vault, &mut position, owner, 100, shares, principal).unwrap();
+        assert_eq!((position.deposited, position.shares), (933, 933));
+        assert_eq!(vault.total_deposits, 1_400);
+        assert_eq!(vault.total_shares, 933);
+        assert_eq!(vault.total_principal, 933);
+
+        assert_eq!(
+            prepare_withdraw(&protocol_config(), &vault, &position, owner, 1_401).unwrap_err(),
+            error!(TeraniumError::InsufficientDepositedBalance)
+        );
+        assert_eq!(
+            prepare_withdraw(&protocol_config(), &vault, &position, Pubkey::new_unique(), 1).unwrap_err(),
+            error!(TeraniumError::Unauthorized)
+        );
+
+        vault.total_deposits = 0;
+        assert_eq!(
+            prepare_withdraw(&protocol_config(), &vault, &position, owner, 1).unwrap_err(),
+            error!(TeraniumError::VaultDepleted)
+        );
+    }
+
+    #[test]
+    fn vaults_close_with_rounding_dust_left_after_the_last_share() {
+        let owner = Pubkey::new_unique();
+        let mut vault = vault_account(100, 3, 100);
+        let mut position = position(owner, &vault, 100, 3);
+        vault.decommissioned = true;
+        assert_eq!(prepare_close_vault(&mut vault).unwrap_err(), error!(TeraniumError::VaultNotEmpty));
+
+        // 99 of 100 assets cost ceil(99 * 3 / 100) = 3 shares, leaving 1 unit with no shares.
+        let (shares, principal) = prepare_withdraw(&protocol_config(), &vault, &position, owner, 99).unwrap();
+        assert_eq!((shares, principal), (3, 100));
+        debit_withdraw(&mut vault, &mut position, owner, 99, shares, principal).unwrap();
+        assert_eq!((vault.total_deposits, vault.total_shares, vault.total_principal), (1, 0, 0));
+
+        vault.pair_count = 1;
+        assert_eq!(prepare_close_vault(&mut vault).unwrap_err(), error!(TeraniumError::VaultInUse));
+        vault.pair_count = 0;
+
+        prepare_close_vault(&mut vault).unwrap();
+        assert_eq!((vault.total_deposits, vault.accrued_fees), (0, 1));
+    }
+
+    #[test]
+    fn positions_forfeit_shares_worth_less_than_one_unit() {
+        let owner = Pubkey::new_unique();
+        // Swap losses left 100 assets for 300 shares: 2 shares are worth 0.67 units.
+        let mut vault = vault_account(100, 300, 300);
+        let mut dust = position(owner, &vault, 2, 2);
+        assert_eq!(
+            prepare_withdraw(&protocol_config(), &vault, &dust, owner, 1).unwrap_err(),
+            error!(TeraniumError::InsufficientDepositedBalance)
+        );
+
+        let mut other = position(Pubkey::new_unique(), &vault, 3, 3);
+        assert_eq!(
+            forfeit_dust_shares(&mut vault, &mut other).unwrap_err(),
+            error!(TeraniumError::PositionNotEmpty)
+        );
+
+        forfeit_dust_shares(&mut vault, &mut dust).unwrap();
+        assert_eq!((dust.shares, dust.deposited), (0, 0));
+        assert_eq!((vault.total_deposits, vault.total_shares, vault.total_principal), (100, 298, 298));
+
+        let mut foreign = position(owner, &vault_account(100, 300, 300), 2, 2);
+        assert_eq!(
+            forfeit_dust_shares(&mut vault, &mut foreign).unwrap_err(),
+            error!(TeraniumError::InvalidUserPosition)
+        );
+    }
+
+    #[test]
+    fn vaults_close_after_the_last_position_forfeits_its_dust() {
+        let owner = Pubkey::new_unique();
+        // Swap losses drained the vault's LP assets; only fees remain.
+        let mut vault = vault_account(0, 3, 3);
+        vault.accrued_fees = 2;
+        vault.decommissioned = true;
+        let mut position = position(owner, &vault, 3, 3);
+
+        forfeit_dust_shares(&mut vault, &mut position).unwrap();
+        prepare_close_vault(&mut vault).unwrap();
+        assert_eq!((vault.total_deposits, vault.total_shares, vault.accrued_fees), (0, 0, 2));
+    }
+
+    #[test]
+    fn deposit_caps_apply_to_principal_and_report_headroom() {
+        let owner = Pubkey::new_unique();
+        // Swap losses left fewer assets than principal; the cap still counts the principal.
+        let mut vault = vault_account(400, 900, 900);
+        vault.max_total_deposits = 1_000;
+        let mut position = unclaimed_position();
+
+        let err = prepare_deposit(&protocol_config(), &vault, &mut position, owner, 101).unwrap_err();
+        assert_eq!(err, error!(TeraniumError::DepositCapExceeded));
+        let Error::AnchorError(err) = err else { panic!("expected an AnchorError") };
+        assert!(matches!(
+            err.compared_values,
+            Some(ComparedValues::Values((ref amount, ref headroom))) if amount == "101" && headroom == "100"
+        ));
+        prepare_deposit(&protocol_config(), &vault, &mut position, owner, 100).unwrap();
+
+        vault.max_deposit_per_user = 250;
+        position.deposited = 200;
+        let err = prepare_deposit(&protocol_config(), &vault, &mut position, owner, 51).unwrap_err();
+        assert_eq!(err, error!(TeraniumError::DepositCapExceeded));
+        prepare_deposit(&protocol_config(), &vault, &mut position, owner, 50).unwrap();
+    }
+
+    /// A pair whose last swap executed at 100.00000000 at t = 1_000, with a 5% / 60 second breaker.
+    fn published(publish_time: i64, publish_slot: u64) -> oracle::OraclePrice {
+        oracle::OraclePrice { publish_time, publish_slot, ..oracle_price(100, -2) }
+    }
+
+    fn clock(unix_timestamp: i64, slot: u64) -> Clock {
+        Clock { unix_timestamp, slot, ..Clock::default() }
+    }
+
+    #[test]
+    fn staleness_limits_apply_to_seconds_and_slots_independently() {
+        let mut pair = pair_account();
+        pair.max_staleness_seconds = 60;
+        pair.max_staleness_slots = 150;
+        let stale = error!(TeraniumError::OracleStale);
+
+        check_staleness(&pair, &published(1_000, 5_000), &clock(1_060, 5_150)).unwrap();
+        assert_eq!(check_staleness(&pair, &published(1_000, 5_000), &clock(1_061, 5_000)).unwrap_err(), stale);
+        assert_eq!(check_staleness(&pair, &published(1_000, 5_000), &clock(1_000, 5_151)).unwrap_err(), stale);
+
+        // A zero limit disables that check only.
+        pair.max_staleness_slots = 0;
+        check_staleness(&pair, &published(1_000, 5_000), &clock(1_060, u64::MAX)).unwrap();
+        assert_eq!(check_staleness(&pair, &published(1_000, 5_000), &clock(1_061, 5_000)).unwrap_err(), stale);
+
+        pair.max_staleness_seconds = 0;
+        pair.max_staleness_slots = 150;
+        check_staleness(&pair, &published(1_000, 5_000), &clock(i64::MAX, 5_150)).unwrap();
+        assert_eq!(check_staleness(&pair, &published(1_000, 5_000), &clock(1_000, 5_151)).unwrap_err(), stale);
+    }
+
+    #[test]
+    fn staleness_accepts_readings_published_ahead_of_the_clock() {
+        let mut pair = pair_account();
+        pair.max_staleness_seconds = 60;
+        pair.max_staleness_slots = 150;
+
+        // Publisher clocks and slots can run slightly ahead of the validator's; a negative age is fresh.
+        check_staleness(&pair, &published(1_010, 5_010), &clock(1_000, 5_000)).unwrap();
+        // An age that overflows is rejected rather than wrapping.
+        let err = check_staleness(&pair, &published(i64::MIN, 0), &clock(1, 0)).unwrap_err();
+        assert_eq!(err, error!(TeraniumError::OracleStale));
+    }
+
+    fn breaker_pair() -> Account<'static, PairConfig> {
+        let mut pair = pair_account();
+        pair.max_price_jump_bps = 500;
+        pair.circuit_breaker_window_seconds = 60;
+        pair.last_price = 10_000_000_000;
+        pair.last_price_expo = -8;
+        pair.last_price_timestamp = 1_000;
+        pair
+    }
+
+    #[test]
+    fn price_jumps_trip_the_breaker_inside_the_window() {
+        let pair = breaker_pair();
+        for (price, now, exceeded) in [
+            (10_500_000_000, 1_030, false), // exactly 5%
+            (9_500_000_000, 1_030, false),
+            (10_500_000_001, 1_030, true),
+            (9_499_999_999, 1_030, true),
+            (20_000_000_000, 1_060, true), // last second of the window
+        ] {
+            assert_eq!(price_jump_exceeded(&pair, &oracle_price(price, -8), now).unwrap(), exceeded);
+        }
+    }
+
+    #[test]
+    fn price_jumps_outside_the_window_or_without_a_reference_are_ignored() {
+        let mut pair = breaker_pair();
+        assert!(!price_jump_exceeded(&pair, &oracle_price(20_000_000_000, -8), 1_061).unwrap());
+        // A changed exponent (e.g. after rebinding) is not comparable.
+        assert!(!price_jump_exceeded(&pair, &oracle_price(200_000_000, -6), 1_030).unwrap());
+
+        pair.last_price_timestamp = 0;
+        assert!(!price_jump_exceeded(&pair, &oracle_price(20_000_000_000, -8), 0).unwrap());
+        assert!(!price_jump_exceeded(&pair, &oracle_price(20_000_000_000, -8), 30).unwrap());
+
+        let mut pair = breaker_pair();
+        pair.max_price_jump_bps = 0;
+        assert!(!price_jump_exceeded(&pair, &oracle_price(20_000_000_000, -8), 1_030).unwrap());
+    }
+
+    #[test]
+    fn tripped_breakers_stay_tripped_past_the_window() {
+        let mut pair = breaker_pair();
+        let jump = oracle_price(20_000_000_000, -8);
+        trip_pair_circuit_breaker(&mut pair, Some(&jump), Pubkey::default(), 1_030);
+        assert!(pair.circuit_breaker_tripped);
+
+        // The reference expiring does not release the pause; only `reset_circuit_breaker` clears the flag.
+        assert!(!price_jump_exceeded(&pair, &jump, 1_061).unwrap());
+        assert!(pair.circuit_breaker_tripped);
+
+        let mut pair = pair_account();
+        trip_pair_circuit_breaker(&mut pair, None, Pubkey::new_unique(), 1_030);
+        assert!(pair.circuit_breaker_tripped);
+    }
+
+    fn volume_capped_pair() -> Account<'static, PairConfig> {
+        let mut pair = pair_account();
+        pair.max_window_notional = 1_000;
+        pair.volume_window_seconds = 60; // six 10-second buckets
+        pair
+    }
+
+    #[test]
+    fn volume_caps_apply_per_direction_over_the_window() {
+        let mut pair = volume_capped_pair();
+        let capped = error!(TeraniumError::VolumeCapExceeded);
+
+        // Bucket 120 maps to slot 0 of the ring.
+        record_volume(&mut pair, 1_200, true, 400).unwrap();
+        record_volume(&mut pair, 1_209, true, 100).unwrap();
+        record_volume(&mut pair, 1_210, true, 500).unwrap();
+        assert_eq!(record_volume(&mut pair, 1_225, true, 1).unwrap_err(), capped);
+        // The other direction has its own budget.
+        record_volume(&mut pair, 1_225, false, 1_000).unwrap();
+        assert_eq!(record_volume(&mut pair, 1_225, false, 1).unwrap_err(), capped);
+
+        assert_eq!(pair.volume_bucket, 122);
+        assert_eq!(pair.base_to_quote_volume, [500, 500, 0, 0, 0, 0]);
+        assert_eq!(pair.quote_to_base_volume, [0, 0, 1_000, 0, 0, 0]);
+    }
+
+    #[test]
+    fn volume_buckets_expire_as_time_advances() {
+        let mut pair = volume_capped_pair();
+        let capped = error!(TeraniumError::VolumeCapExceeded);
+        record_volume(&mut pair, 1_200, true, 400).unwrap();
+        record_volume(&mut pair, 1_210, true, 600).unwrap();
+
+        // One second short of a full window the first bucket still counts.
+        assert_eq!(record_volume(&mut pair, 1_259, true, 1).unwrap_err(), capped);
+        // Rolling into bucket 126 reuses slot 0 and frees its 400.
+        record_volume(&mut pair, 1_260, true, 400).unwrap();
+        assert_eq!(record_volume(&mut pair, 1_260, true, 1).unwrap_err(), capped);
+        assert_eq!(pair.base_to_quote_volume, [400, 600, 0, 0, 0, 0]);
+
+        // Skipping several buckets clears every one passed over.
+        record_volume(&mut pair, 1_290, false, 1).unwrap();
+        assert_eq!(pair.volume_bucket, 129);
+        assert_eq!(pair.base_to_quote_volume, [400, 0, 0, 0, 0, 0]);
+
+        // More than a full window later nothing is left.
+        record_volume(&mut pair, 10_000, true, 1_000).unwrap();
+        assert_eq!(pair.base_to_quote_volume.iter().sum::<u64>(), 1_000);
+        assert_eq!(pair.quote_to_base_volume, [0; VOLUME_BUCKETS]);
+    }
+
+    #[test]
+    fn volume_is_not_tracked_without_a_cap() {
+        let mut pair = pair_account();
+        record_volume(&mut pair, 1_200, true, u64::MAX).unwrap();
+        record_volume(&mut pair, 1_200, true, u64::MAX).unwrap();
+        assert_eq!(pair.volume_bucket, 0);
+        assert_eq!(pair.base_to_quote_volume, [0; VOLUME_BUCKETS]);
+    }
+
+    fn fallback_pair() -> Account<'static, PairConfig> {
+        let mut pair = breaker_pair();
+        pair.max_fallback_deviation_bps = 200;
+        pair.max_fallback_trade_notional = 5_000;
+        pair
+    }
+
+    #[test]
+    fn fallback_prices_stay_within_the_deviation_bound() {
+        let pair = fallback_pair();
+        // 2% either side of the last oracle price (100.0 at expo -8).
+        check_fallback_price(&pair, 10_200_000_000, -8, 60).unwrap();
+        check_fallback_price(&pair, 9_800_000_000, -8, MAX_FALLBACK_PRICE_TTL_SECONDS).unwrap();
+        for price in [10_200_000_001, 9_799_999_999, i64::MAX] {
+            let err = check_fallback_price(&pair, price, -8, 60).unwrap_err();
+            assert_eq!(err, error!(TeraniumError::FallbackPriceDeviation));
+        }
+
+        let too_long = MAX_FALLBACK_PRICE_TTL_SECONDS + 1;
+        for (price, ttl) in [(0, 60), (-1, 60), (10_000_000_000, 0), (10_000_000_000, too_long)] {
+            let err = check_fallback_price(&pair, price, -8, ttl).unwrap_err();
+            assert_eq!(err, error!(TeraniumError::InvalidFallbackPrice));
+        }
+    }
+
+    #[test]
+    fn fallback_prices_need_a_comparable_reference() {
+        let pair = fallback_pair();
+        let err = check_fallback_price(&pair, 1_000_000_000, -7, 60).unwrap_err();
+        assert_eq!(err, error!(TeraniumError::FallbackReferenceUnavailable));
+
+        let mut pair = fallback_pair();
+        pair.last_price = 0;
+        let err = check_fallback_price(&pair, 10_000_000_000, -8, 60).unwrap_err();
+        assert_eq!(err, error!(TeraniumError::FallbackReferenceUnavailable));
+
+        let mut pair = fallback_pair();
+        pair.max_fallback_deviation_bps = 0;
+        let err = check_fallback_price(&pair, 10_000_000_000, -8, 60).unwrap_err();
+        assert_eq!(err, error!(TeraniumError::FallbackPriceDisabled));
+    }
+
+    #[test]
+    fn fallback_prices_apply_until_they_expire() {
+        let mut pair = fallback_pair();
+        pair.fallback_price = 10_100_000_000;
+        pair.fallback_conf = 1_000;
+        pair.fallback_expo = -8;
+        pair.fallback_expires_at = 2_000;
+
+        let price = active_fallback_price(&pair, &clock(1_999, 42)).unwrap();
+        assert_eq!((price.price, price.conf, price.expo), (10_100_000_000, 1_000, -8));
+        // Reported as published now so the staleness checks do not reject it.
+        assert_eq!((price.publish_time, price.publish_slot, price.ema_price), (1_999, 42, None));
+
+        assert!(active_fallback_price(&pair, &clock(2_000, 43)).is_none());
+        pair.fallback_expires_at = 0;
+        assert!(active_fallback_price(&pair, &clock(0, 0)).is_none());
+    }
+
+    #[test]
+    fn fallback_swaps_are_capped_at_the_fallback_notional() {
+        let mut pair = fallback_pair();
+        let too_large = error!(TeraniumError::TradeSizeExceeded);
+        check_trade_notional(&pair, u64::MAX, false).unwrap();
+        check_trade_notional(&pair, 5_000, true).unwrap();
+        assert_eq!(check_trade_notional(&pair, 5_001, true).unwrap_err(), too_large);
+
+        // The tighter of the two caps applies.
+        pair.max_trade_notional = 3_000;
+        check_trade_notional(&pair, 3_000, true).unwrap();
+        assert_eq!(check_trade_notional(&pair, 3_001, true).unwrap_err(), too_large);
+        assert_eq!(check_trade_notional(&pair, 3_001, false).unwrap_err(), too_large);
+    }
+
+    fn mint_account(owner: &'static Pubkey, data: Vec<u8>) -> InterfaceAccount<'static, Mint> {
+        let info = AccountInfo::new(
+            Box::leak(Box::new(Pubkey::new_unique())),
+            false,
+            false,
+            Box::leak(Box::new(0)),
+            Box::leak(data.into_boxed_slice()),
+            owner,
+            false,
+            0,
+        );
+        InterfaceAccount::try_from(&*Box::leak(Box::new(info))).unwrap()
+    }
+
+    /// A Token-2022 mint sized for `extensions`; `init` initializes them.
+    fn token_2022_mint(
+        extensions: &[ExtensionType],
+        freeze_authority: Option<Pubkey>,
+        init: impl FnOnce(&mut StateWithExtensionsMut<spl_token_2022::state::Mint>),
+    ) -> InterfaceAccount<'static, Mint> {
+        let len = ExtensionType::try_calculate_account_len::<spl_token_2022::state::Mint>(extensions).unwrap();
+        let mut data = vec![0; len];
+        let mut state = StateWithExtensionsMut::<spl_token_2022::state::Mint>::unpack_uninitialized(&mut data).unwrap();
+        init(&mut state);
+        state.base = spl_token_2022::state::Mint {
+            decimals: 6,
+            is_initialized: true,
+            freeze_authority: freeze_authority.into(),
+            ..Default::default()
+        };
+        state.pack_base();
+        state.init_account_type().unwrap();
+        mint_account(&spl_token_2022::ID, data)
+    }
+
+    #[test]
+    fn mints_with_allowed_extensions_are_accepted() {
+        let plain = token_2022_mint(&[], None, |_| {});
+        check_mint_extensions(&plain).unwrap();
+
+        let mint = token_2022_mint(
+            &[ExtensionType::TransferFeeConfig, ExtensionType::TransferHook, ExtensionType::DefaultAccountState],
+            None,
+            |state| {
+                let fee = state.init_extension::<TransferFeeConfig>(true).unwrap();
+                fee.newer_transfer_fee.transfer_fee_basis_points = 50.into();
+                let hook = state.init_extension::<transfer_hook::TransferHook>(true).unwrap();
+                hook.program_id = Some(Pubkey::new_unique()).try_into().unwrap();
+                state.init_extension::<DefaultAccountState>(true).unwrap().state = AccountState::Initialized as u8;
+            },
+        );
+        check_mint_extensions(&mint).unwrap();
+
+        // Legacy SPL Token mints carry no extensions and are not screened.
+        let mut data = vec![0; spl_token_2022::state::Mint::LEN];
+        let base = spl_token_2022::state::Mint {
+            is_initialized: true,
+            freeze_authority: Some(Pubkey::new_unique()).into(),
+            ..Default::default()
+        };
+        spl_token_2022::state::Mint::pack(base, &mut data).unwrap();
+        check_mint_extensions(&mint_account(&anchor_spl::token::ID, data)).unwrap();
+    }
+
+    #[test]
+    fn mints_with_disallowed_extensions_are_rejected() {
+        let unsupported = error!(TeraniumError::UnsupportedMint);
+
+        let mint = token_2022_mint(&[ExtensionType::PermanentDelegate], None, |state| {
+            state.init_extension::<PermanentDelegate>(true).unwrap().delegate =
+                Some(Pubkey::new_unique()).try_into().unwrap();
+        });
+        assert_eq!(check_mint_extensions(&mint).unwrap_err(), unsupported);
+
+        let mint = token_2022_mint(&[ExtensionType::TransferFeeConfig, ExtensionType::NonTransferable], None, |state| {
+            state.init_extension::<TransferFeeConfig>(true).unwrap();
+            state.init_extension::<NonTransferable>(true).unwrap();
+        });
+        assert_eq!(check_mint_extensions(&mint).unwrap_err(), unsupported);
+
+        // Allowed extensions can still be configured in a way that would lock vault funds.
+        let mint = token_2022_mint(&[ExtensionType::DefaultAccountState], None, |state| {
+            state.init_extension::<DefaultAccountState>(true).unwrap().state = AccountState::Frozen as u8;
+        });
+        assert_eq!(check_mint_extensions(&mint).unwrap_err(), unsupported);
+
+        let mint = token_2022_mint(&[], Some(Pubkey::new_unique()), |_| {});
+        assert_eq!(check_mint_extensions(&mint).unwrap_err(), unsupported);
+    }
+
+    #[test]
+    fn param_changes_are_validated_at_their_bounds() {
+        let valid = [
+            ParamChange::PairFee { fee_bps: MAX_FEE_BPS },
+            ParamChange::PairPricing {
+                pricing_mode: PricingMode::ConfidenceAdjusted as u8,
+                conf_multiplier_bps: MAX_CONF_MULTIPLIER_BPS,
+            },
+            ParamChange::PairStaleness {
+                max_staleness_seconds: 0,
+                max_staleness_slots: MAX_STALENESS_SLOTS,
+            },
+            ParamChange::PairStaleness {
+                max_staleness_seconds: MAX_STALENESS_SECONDS,
+                max_staleness_slots: 0,
+            },
+            ParamChange::PairEmaGuard { max_ema_deviation_bps: 10_000 },
+            ParamChange::PairVolumeCaps {
+                max_trade_notional: 1,
+                max_window_notional: 1,
+                volume_window_seconds: VOLUME_BUCKETS as u32,
+            },
+            ParamChange::PairVolumeCaps {
+                max_trade_notional: 0,
+                max_window_notional: 0,
+                volume_window_seconds: 0,
+            },
+            ParamChange::PairCircuitBreaker {
+                max_price_jump_bps: 10_000,
+                circuit_breaker_window_seconds: MAX_CIRCUIT_BREAKER_WINDOW_SECONDS,
+            },
+            ParamChange::PairCircuitBreaker {
+                max_price_jump_bps: 0,
+                circuit_breaker_window_seconds: 0,
+            },
+            ParamChange::PairFallbackBounds {
+                max_fallback_deviation_bps: 0,
+                max_fallback_trade_notional: 0,
+            },
+            ParamChange::PairFallbackBounds {
+                max_fallback_deviation_bps: 10_000,
+                max_fallback_trade_notional: 1,
+            },
+            ParamChange::Timelock { timelock_seconds: MIN_TIMELOCK_SECONDS },
+            ParamChange::Timelock { timelock_seconds: MAX_TIMELOCK_SECONDS },
+        ];
+        for change in valid {
+            change.validate().unwrap();
+        }
+
+        let invalid = [
+            (ParamChange::PairFee { fee_bps: MAX_FEE_BPS + 1 }, TeraniumError::InvalidFeeBps),
+            (
+                ParamChange::PairPricing {
+                    pricing_mode: 2,
+                    conf_multiplier_bps: 0,
+                },
+                TeraniumError::InvalidPricingMode,
+            ),
+            (
+                ParamChange::PairPricing {
+                    pricing_mode: PricingMode::Mid as u8,
+                    conf_multiplier_bps: MAX_CONF_MULTIPLIER_BPS + 1,
+                },
+                TeraniumError::InvalidPricingMode,
+            ),
+            (
+                ParamChange::PairSecondaryOracle {
+                    oracle: ChangeOracle {
+                        kind: OracleKind::PythLegacy as u8,
+                        oracle: Pubkey::new_unique(),
+                        feed_id: [0; 32],
+                        min_verification_signatures: 0,
+                    },
+                    max_deviation_bps: 0,
+                    dual_oracle_pricing: DualOraclePricing::Median as u8,
+                },
+                TeraniumError::InvalidDeviationBps,
+            ),
+            (
+                ParamChange::PairStaleness {
+                    max_staleness_seconds: 0,
+                    max_staleness_slots: 0,
+                },
+                TeraniumError::InvalidStaleness,
+            ),
+            (
+                ParamChange::PairStaleness {
+                    max_staleness_seconds: MAX_STALENESS_SECONDS + 1,
+                    max_staleness_slots: 0,
+                },
+                TeraniumError::InvalidStaleness,
+            ),
+            (
+                ParamChange::PairEmaGuard { max_ema_deviation_bps: 10_001 },
+                TeraniumError::InvalidDeviationBps,
+            ),
+            (
+                ParamChange::PairVolumeCaps {
+                    max_trade_notional: 0,
+                    max_window_notional: 1,
+                    volume_window_seconds: VOLUME_BUCKETS as u32 - 1,
+                },
+                TeraniumError::InvalidVolumeCaps,
+            ),
+            (
+                ParamChange::PairVolumeCaps {
+                    max_trade_notional: 0,
+                    max_window_notional: 1,
+                    volume_window_seconds: MAX_VOLUME_WINDOW_SECONDS + 1,
+                },
+                TeraniumError::InvalidVolumeCaps,
+            ),
+            (
+                ParamChange::PairCircuitBreaker {
+                    max_price_jump_bps: 10_001,
+                    circuit_breaker_window_seconds: 60,
+                },
+                TeraniumError::InvalidCircuitBreaker,
+            ),
+            (
+                ParamChange::PairCircuitBreaker {
+                    max_price_jump_bps: 500,
+                    circuit_breaker_window_seconds: 0,
+                },
+                TeraniumError::InvalidCircuitBreaker,
+            ),
+            (
+                ParamChange::PairFallbackBounds {
+                    max_fallback_deviation_bps: 500,
+                    max_fallback_trade_notional: 0,
+                },
+                TeraniumError::InvalidFallbackBounds,
+            ),
+            (
+                ParamChange::PairFallbackBounds {
+                    max_fallback_deviation_bps: 10_001,
+                    max_fallback_trade_notional: 1,
+                },
+                TeraniumError::InvalidFallbackBounds,
+            ),
+            (
+                ParamChange::Timelock { timelock_seconds: MIN_TIMELOCK_SECONDS - 1 },
+                TeraniumError::InvalidTimelock,
+            ),
+            (
+                ParamChange::Timelock { timelock_seconds: MAX_TIMELOCK_SECONDS + 1 },
+                TeraniumError::InvalidTimelock,
+            ),
+        ];
+        for (change, expected) in invalid {
+            assert_eq!(change.validate().unwrap_err(), Error::from(expected));
+        }
+    }
+
+    #[test]
+    fn exact_out_gross_is_the_smallest_that_covers_the_net() {
+        let net_out = |gross: u64, fee_bps: u16| gross - swap_fee(gross, fee_bps).unwrap();
+        for fee_bps in [0, 1, 30, MAX_FEE_BPS, 9_999] {
+            for amount_out in [1, 2, 3, 9_999, 10_000, 10_001, 123_456_789, u64::MAX / 10_000, u64::MAX - 1, u64::MAX] {
+                // Closed form of the smallest covering gross, in u128; the net_out checks confirm it.
+                let net_bps = 10_000 - fee_bps as u128;
+                let minimal = (amount_out as u128 - 1) * 10_000 / net_bps + 1;
+                match gross_out_for_net(amount_out, fee_bps) {
+                    Ok(gross) => {
+                        assert_eq!(gross as u128, minimal, "fee_bps {fee_bps}, amount_out {amount_out}");
+                        assert!(net_out(gross, fee_bps) >= amount_out);
+                        assert!(net_out(gross - 1, fee_bps) < amount_out);
+                    }
+                    Err(err) => {
+                        assert_eq!(err, error!(TeraniumError::MathOverflow));
+                        assert!(minimal > u64::MAX as u128, "fee_bps {fee_bps}, amount_out {amount_out}");
+                    }
+                }
+            }
+            assert_eq!(gross_out_for_net(0, fee_bps).unwrap(), 0);
+        }
+        // One unit out at 1 bps costs no fee, exactly like the exact-in path.
+        assert_eq!(gross_out_for_net(1, 1).unwrap(), 1);
+        assert_eq!(gross_out_for_net(u64::MAX, 0).unwrap(), u64::MAX);
+    }
+
+    #[test]
+    fn exact_out_inputs_round_up_to_cover_the_output() {
+        // (price, expo, base_decimals, quote_decimals)
+        let markets = [
+            (15_025_000_000u128, -8, 9, 6),
+            (1u128, -8, 6, 6),
+            (99_999_999u128, -8, 6, 9),
+            (3u128, 2, 0, 6),
+        ];
+        for (price, expo, base_decimals, quote_decimals) in markets {
+            for gross in [1u128, 7, 1_000_000, 123_456_789_012, u64::MAX as u128] {
+                // Quote out, base in: the base charged is worth at least the quote paid, and one unit less is not.
+                let base_in = quote_to_base(gross, price, expo, base_decimals, quote_decimals, true).unwrap();
+                assert!(base_to_quote(base_in, price, expo, base_decimals, quote_decimals, false).unwrap() >= gross);
+                if base_in > 0 {
+                    let below = base_to_quote(base_in - 1, price, expo, base_decimals, quote_decimals, false).unwrap();
+                    assert!(below < gross);
+                }
+
+                // Base out, quote in.
+                let quote_in = base_to_quote(gross, price, expo, base_decimals, quote_decimals, true).unwrap();
+                assert!(quote_to_base(quote_in, price, expo, base_decimals, quote_decimals, false).unwrap() >= gross);
+                if quote_in > 0 {
+                    let below = quote_to_base(quote_in - 1, price, expo, base_decimals, quote_decimals, false).unwrap();
+                    assert!(below < gross);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn execution_price_applies_the_confidence_spread_against_the_trader() {
+        let mid = PricingMode::Mid as u8;
+        let adjusted = PricingMode::ConfidenceAdjusted as u8;
+
+        // Mid ignores confidence and multiplier, even when conf exceeds the price.
+        for vault_buys_base in [true, false] {
+            assert_eq!(execution_price(100, 500, mid, MAX_CONF_MULTIPLIER_BPS, vault_buys_base).unwrap(), 100);
+        }
+
+        // k = 1.5: spread = ceil(40 * 1.5) = 60, bid below and ask above the mid.
+        assert_eq!(execution_price(1_000, 40, adjusted, 15_000, true).unwrap(), 940);
+        assert_eq!(execution_price(1_000, 40, adjusted, 15_000, false).unwrap(), 1_060);
+        // The spread rounds up, in the vault's favor on both sides.
+        assert_eq!(execution_price(1_000, 1, adjusted, 1, true).unwrap(), 999);
+        assert_eq!(execution_price(1_000, 1, adjusted, 1, false).unwrap(), 1_001);
+        assert_eq!(execution_price(1_000, 40, adjusted, 0, true).unwrap(), 1_000);
+
+        let invalid = error!(TeraniumError::OracleInvalidPrice);
+        // conf >= price: the bid must fail rather than reach 0 or wrap; the ask stays defined.
+        assert_eq!(execution_price(100, 100, adjusted, 10_000, true).unwrap_err(), invalid);
+        assert_eq!(execution_price(100, 150, adjusted, 10_000, true).unwrap_err(), invalid);
+        assert_eq!(execution_price(100, 150, adjusted, 10_000, false).unwrap(), 250);
+        // The largest multiplier (k = 5) turns a 20% confidence band into a zero bid.
+        assert_eq!(execution_price(100, 20, adjusted, MAX_CONF_MULTIPLIER_BPS, true).unwrap_err(), invalid);
+        assert_eq!(execution_price(100, 19, adjusted, MAX_CONF_MULTIPLIER_BPS, true).unwrap(), 5);
+        assert_eq!(execution_price(100, 20, adjusted, MAX_CONF_MULTIPLIER_BPS, false).unwrap(), 200);
+        // Out-of-range inputs fail instead of wrapping.
+        assert_eq!(
+            execution_price(1, u128::MAX, adjusted, u16::MAX, false).unwrap_err(),
+            error!(TeraniumError::MathOverflow)
+        );
+        assert_eq!(
+            execution_price(u128::MAX, 10_000, adjusted, 10_000, false).unwrap_err(),
+            error!(TeraniumError::MathOverflow)
+        );
+
+        assert_eq!(
+            execution_price(100, 1, 2, 10_000, true).unwrap_err(),
+            error!(TeraniumError::InvalidPricingMode)
+        );
+    }
 }
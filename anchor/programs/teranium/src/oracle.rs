@@ -0,0 +1,794 @@
+//! Oracle adapters. Every supported source is read into an `OraclePrice` so swap pricing,
+//! staleness and confidence checks do not depend on where the price came from.
+//!
+//! The account owner is checked by the caller against `OracleBinding::owner`, which `bind` derives
+//! from the source kind; adapters enforce the rest of the binding (address or feed id) and parse the account.
+
+use anchor_lang::prelude::*;
+use pyth_sdk_solana::state::{load_price_account, PriceStatus};
+
+use crate::{pow10_u128, DualOraclePricing, OracleBinding, OracleKind, TeraniumError, BPS_DENOM};
+
+// Programs that own each source's accounts: mainnet-beta (also localnet, which clones mainnet accounts),
+// or the devnet deployments in `devnet` builds. The Pyth receiver has the same id on both clusters.
+#[cfg(not(feature = "devnet"))]
+const PYTH_LEGACY_PROGRAM_ID: Pubkey = pubkey!("FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH");
+#[cfg(feature = "devnet")]
+const PYTH_LEGACY_PROGRAM_ID: Pubkey = pubkey!("gSbePebfvPy7tRqimPoVecS2UsBvYv46ynrzWocc92s");
+const PYTH_RECEIVER_PROGRAM_ID: Pubkey = pubkey!("rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ");
+#[cfg(not(feature = "devnet"))]
+const SWITCHBOARD_ON_DEMAND_PROGRAM_ID: Pubkey = pubkey!("SBondMDrcV3K4kxZR1HNVT7osZxAHVHgYXL5Ze1oMUv");
+#[cfg(feature = "devnet")]
+const SWITCHBOARD_ON_DEMAND_PROGRAM_ID: Pubkey = pubkey!("Aio4gaXjXzJNVLtzwtNVmSqGKpANtXhybbkhtAC94ji2");
+
+// Anchor account discriminator of the Pyth receiver's `PriceUpdateV2` (sha256("account:PriceUpdateV2")[..8]).
+const PRICE_UPDATE_V2_DISCRIMINATOR: [u8; 8] = [34, 241, 35, 99, 157, 126, 244, 205];
+
+// Anchor account discriminator of Switchboard On-Demand's `PullFeedAccountData`.
+const SWITCHBOARD_PULL_FEED_DISCRIMINATOR: [u8; 8] = [196, 27, 108, 196, 10, 215, 219, 40];
+
+// Byte offsets into `PullFeedAccountData` (after the discriminator).
+const SWITCHBOARD_RESULT_OFFSET: usize = 2256;
+const SWITCHBOARD_RESULT_VALUE_OFFSET: usize = SWITCHBOARD_RESULT_OFFSET;
+const SWITCHBOARD_RESULT_STD_DEV_OFFSET: usize = SWITCHBOARD_RESULT_OFFSET + 16;
+const SWITCHBOARD_RESULT_SUBMISSION_IDX_OFFSET: usize = SWITCHBOARD_RESULT_OFFSET + 97;
+const SWITCHBOARD_RESULT_SLOT_OFFSET: usize = SWITCHBOARD_RESULT_OFFSET + 104;
+const SWITCHBOARD_SUBMISSION_TIMESTAMPS_OFFSET: usize = 2944;
+const SWITCHBOARD_PULL_FEED_LEN: usize = SWITCHBOARD_SUBMISSION_TIMESTAMPS_OFFSET + 32 * 8;
+
+// Switchboard values are fixed point with 18 decimals; they are rescaled to this exponent.
+const SWITCHBOARD_PRECISION: u32 = 18;
+const SWITCHBOARD_EXPO: i32 = -8;
+
+/// Oracle reading normalized across sources: `price ± conf` scaled by `10^expo`.
+#[derive(Clone, Copy)]
+pub(crate) struct OraclePrice {
+    pub price: i64,
+    pub conf: u64,
+    pub expo: i32,
+    pub publish_time: i64,
+    /// Slot the price was published (or posted) at, for slot-based staleness.
+    pub publish_slot: u64,
+    /// EMA price in the same exponent, when the source publishes one.
+    pub ema_price: Option<i64>,
+}
+
+trait OracleAdapter {
+    fn load(binding: &OracleBinding, oracle: &AccountInfo) -> Result<OraclePrice>;
+}
+
+/// Reads an oracle price through the adapter selected by `binding.kind`.
+pub(crate) fn load_price(binding: &OracleBinding, oracle: &AccountInfo) -> Result<OraclePrice> {
+    match binding.kind {
+        k if k == OracleKind::PythLegacy as u8 => PythLegacy::load(binding, oracle),
+        k if k == OracleKind::PythPull as u8 => PythPull::load(binding, oracle),
+        k if k == OracleKind::SwitchboardOnDemand as u8 => SwitchboardOnDemand::load(binding, oracle),
+        #[cfg(feature = "localnet")]
+        k if k == OracleKind::Mock as u8 => Mock::load(binding, oracle),
+        _ => err!(TeraniumError::InvalidOracleKind),
+    }
+}
+
+/// Program that must own accounts of the given `OracleKind`; `Mock` prices are owned by this program.
+pub(crate) fn program_owner(kind: u8) -> Result<Pubkey> {
+    match kind {
+        k if k == OracleKind::PythLegacy as u8 => Ok(PYTH_LEGACY_PROGRAM_ID),
+        k if k == OracleKind::PythPull as u8 => Ok(PYTH_RECEIVER_PROGRAM_ID),
+        k if k == OracleKind::SwitchboardOnDemand as u8 => Ok(SWITCHBOARD_ON_DEMAND_PROGRAM_ID),
+        #[cfg(feature = "localnet")]
+        k if k == OracleKind::Mock as u8 => Ok(crate::ID),
+        _ => err!(TeraniumError::InvalidOracleKind),
+    }
+}
+
+/// Builds the binding for `oracle` under the given source settings and checks that it loads.
+///
+/// - The owner is the source's program (`program_owner`), never caller-supplied.
+/// - Address-bound sources bind `oracle`'s key; `feed_id` and `min_verification_signatures` are ignored.
+/// - Pyth pull binds `feed_id` and the verification level instead of an address.
+pub(crate) fn bind(
+    kind: u8,
+    feed_id: [u8; 32],
+    min_verification_signatures: u8,
+    oracle: &AccountInfo,
+) -> Result<OracleBinding> {
+    let owner = program_owner(kind)?;
+    require_keys_eq!(*oracle.owner, owner, TeraniumError::OracleOwnerMismatch);
+
+    let binding = if kind == OracleKind::PythPull as u8 {
+        OracleBinding {
+            kind,
+            oracle: Pubkey::default(),
+            owner,
+            feed_id,
+            min_verification_signatures,
+        }
+    } else {
+        OracleBinding {
+            kind,
+            oracle: oracle.key(),
+            owner,
+            feed_id: [0; 32],
+            min_verification_signatures: 0,
+        }
+    };
+    load_price(&binding, oracle)?;
+
+    Ok(binding)
+}
+
+/// Checks two readings against each other and combines them, in the primary's exponent.
+///
+/// - Fails with `OracleDeviation` when `|primary - secondary| > max_deviation_bps` of the primary price.
+/// - `Conservative`: the price that favors the vault (lower when it buys base, higher when it sells).
+/// - `Median`: the midpoint of the two (the median of two), rounded in the vault's favor, with the wider confidence.
+/// - Publish time and slot are the older of the two, so staleness applies to both.
+/// - The EMA is the primary's, falling back to the secondary's.
+pub(crate) fn combine(
+    primary: OraclePrice,
+    secondary: OraclePrice,
+    max_deviation_bps: u16,
+    pricing: u8,
+    vault_buys_base: bool,
+) -> Result<OraclePrice> {
+    require!(primary.price > 0 && secondary.price > 0, TeraniumError::OracleInvalidPrice);
+
+    let p1 = primary.price as u128;
+    let p2 = rescale(secondary.price as u128, secondary.expo, primary.expo, !vault_buys_base)?;
+    let c1 = primary.conf as u128;
+    let c2 = rescale(secondary.conf as u128, secondary.expo, primary.expo, true)?;
+    let ema_price = match (primary.ema_price, secondary.ema_price) {
+        (Some(ema), _) => Some(ema),
+        (None, Some(ema)) if ema > 0 => {
+            let ema = rescale(ema as u128, secondary.expo, primary.expo, false)?;
+            Some(i64::try_from(ema).map_err(|_| TeraniumError::OracleInvalidPrice)?)
+        }
+        _ => None,
+    };
+
+    // |p1 - p2| * 10_000 <= p1 * max_deviation_bps
+    let diff = p1.abs_diff(p2);
+    require!(
+        diff.checked_mul(BPS_DENOM as u128).ok_or(TeraniumError::MathOverflow)?
+            <= p1.checked_mul(max_deviation_bps as u128).ok_or(TeraniumError::MathOverflow)?,
+        TeraniumError::OracleDeviation
+    );
+
+    let (price, conf) = if pricing == DualOraclePricing::Median as u8 {
+        let sum = p1.checked_add(p2).ok_or(TeraniumError::MathOverflow)?;
+        let mid = if vault_buys_base { sum / 2 } else { sum / 2 + sum % 2 };
+        (mid, c1.max(c2))
+    } else {
+        let pick_secondary = if vault_buys_base { p2 < p1 } else { p2 > p1 };
+        if pick_secondary { (p2, c2) } else { (p1, c1) }
+    };
+
+    Ok(OraclePrice {
+        price: i64::try_from(price).map_err(|_| TeraniumError::OracleInvalidPrice)?,
+        conf: u64::try_from(conf).map_err(|_| TeraniumError::OracleInvalidConfidence)?,
+        expo: primary.expo,
+        publish_time: primary.publish_time.min(secondary.publish_time),
+        publish_slot: primary.publish_slot.min(secondary.publish_slot),
+        ema_price,
+    })
+}
+
+/// Converts `value * 10^from_expo` into units of `10^to_expo`.
+fn rescale(value: u128, from_expo: i32, to_expo: i32, round_up: bool) -> Result<u128> {
+    let diff = from_expo.checked_sub(to_expo).ok_or(TeraniumError::MathOverflow)?;
+    if diff >= 0 {
+        return value
+            .checked_mul(pow10_u128(diff as u32)?)
+            .ok_or(error!(TeraniumError::MathOverflow));
+    }
+    let scale = pow10_u128(diff.unsigned_abs())?;
+    let q = value / scale;
+    Ok(if round_up && q * scale < value { q + 1 } else { q })
+}
+
+/// Legacy Pyth push price account, bound by address.
+struct PythLegacy;
+
+impl OracleAdapter for PythLegacy {
+    fn load(binding: &OracleBinding, oracle: &AccountInfo) -> Result<OraclePrice> {
+        require_keys_eq!(oracle.key(), binding.oracle, TeraniumError::OracleAccountMismatch);
+        let data = oracle.try_borrow_data()?;
+        let account = load_price_account::<32, ()>(&data).map_err(|_| TeraniumError::OracleInvalidAccount)?;
+        // Only a trading aggregate is a price; halted or unknown feeds would otherwise fall back to the previous
+        // update. Pairs keep trading through a halt only on an admin fallback price, which skips this load.
+        require!(account.agg.status == PriceStatus::Trading, TeraniumError::OracleNoPrice);
+        let price_feed = account.to_price_feed(oracle.key);
+        let price = price_feed.get_price_unchecked();
+
+        Ok(OraclePrice {
+            price: price.price,
+            conf: price.conf,
+            expo: price.expo,
+            publish_time: price.publish_time,
+            publish_slot: account.agg.pub_slot,
+            ema_price: Some(price_feed.get_ema_price_unchecked().price),
+        })
+    }
+}
+
+/// Pyth pull `PriceUpdateV2` account, bound by feed id and verification level.
+/// The publish slot is the slot the update was posted at.
+struct PythPull;
+
+impl OracleAdapter for PythPull {
+    fn load(binding: &OracleBinding, oracle: &AccountInfo) -> Result<OraclePrice> {
+        let data = oracle.try_borrow_data()?;
+        require!(
+            data.len() >= 8 && data[..8] == PRICE_UPDATE_V2_DISCRIMINATOR,
+            TeraniumError::OracleInvalidAccount
+        );
+        let update =
+            PriceUpdateV2::deserialize(&mut &data[8..]).map_err(|_| TeraniumError::OracleInvalidAccount)?;
+
+        let message = update.price_message;
+        require!(message.feed_id == binding.feed_id, TeraniumError::OracleFeedMismatch);
+        let verified = match update.verification_level {
+            VerificationLevel::Full => true,
+            VerificationLevel::Partial { num_signatures } => {
+                binding.min_verification_signatures > 0 && num_signatures >= binding.min_verification_signatures
+            }
+        };
+        require!(verified, TeraniumError::OracleVerificationInsufficient);
+
+        Ok(OraclePrice {
+            price: message.price,
+            conf: message.conf,
+            expo: message.exponent,
+            publish_time: message.publish_time,
+            publish_slot: update.posted_slot,
+            ema_price: Some(message.ema_price),
+        })
+    }
+}
+
+/// Switchboard On-Demand pull feed, bound by address.
+///
+/// - Price is the feed's current median result, confidence its standard deviation (rounded up).
+/// - Publish time is the timestamp of the submission the result was taken from; publish slot is
+///   the slot the result was signed at.
+/// - No EMA is published.
+struct SwitchboardOnDemand;
+
+impl OracleAdapter for SwitchboardOnDemand {
+    fn load(binding: &OracleBinding, oracle: &AccountInfo) -> Result<OraclePrice> {
+        require_keys_eq!(oracle.key(), binding.oracle, TeraniumError::OracleAccountMismatch);
+
+        let data = oracle.try_borrow_data()?;
+        require!(
+            data.len() >= 8 + SWITCHBOARD_PULL_FEED_LEN && data[..8] == SWITCHBOARD_PULL_FEED_DISCRIMINATOR,
+            TeraniumError::OracleInvalidAccount
+        );
+        let feed = &data[8..];
+
+        let slot = read_u64(feed, SWITCHBOARD_RESULT_SLOT_OFFSET);
+        require!(slot != 0, TeraniumError::OracleNoPrice);
+
+        let value = read_i128(feed, SWITCHBOARD_RESULT_VALUE_OFFSET);
+        let std_dev = read_i128(feed, SWITCHBOARD_RESULT_STD_DEV_OFFSET);
+        require!(std_dev >= 0, TeraniumError::OracleInvalidConfidence);
+
+        let submission_idx = feed[SWITCHBOARD_RESULT_SUBMISSION_IDX_OFFSET] as usize;
+        require!(submission_idx < 32, TeraniumError::OracleInvalidAccount);
+        let publish_time = read_u64(feed, SWITCHBOARD_SUBMISSION_TIMESTAMPS_OFFSET + submission_idx * 8) as i64;
+
+        let scale = 10i128.pow(SWITCHBOARD_PRECISION - (-SWITCHBOARD_EXPO) as u32);
+        let price = i64::try_from(value / scale).map_err(|_| TeraniumError::OracleInvalidPrice)?;
+        let conf = u64::try_from((std_dev + scale - 1) / scale).map_err(|_| TeraniumError::OracleInvalidConfidence)?;
+
+        Ok(OraclePrice {
+            price,
+            conf,
+            expo: SWITCHBOARD_EXPO,
+            publish_time,
+            publish_slot: slot,
+            ema_price: None,
+        })
+    }
+}
+
+fn read_u64(data: &[u8], offset: usize) -> u64 {
+    let mut buf = [0u8; 8];
+    buf.copy_from_slice(&data[offset..offset + 8]);
+    u64::from_le_bytes(buf)
+}
+
+fn read_i128(data: &[u8], offset: usize) -> i128 {
+    let mut buf = [0u8; 16];
+    buf.copy_from_slice(&data[offset..offset + 16]);
+    i128::from_le_bytes(buf)
+}
+
+/// Program-owned price account for local validator tests. Tests write it directly
+/// (e.g. `solana-test-validator --account`); there is no instruction that updates it.
+#[cfg(feature = "localnet")]
+#[account]
+pub struct MockPrice {
+    pub price: i64,
+    pub conf: u64,
+    pub expo: i32,
+    pub publish_time: i64,
+    pub publish_slot: u64,
+    /// 0 means no EMA.
+    pub ema_price: i64,
+}
+
+#[cfg(feature = "localnet")]
+struct Mock;
+
+#[cfg(feature = "localnet")]
+impl OracleAdapter for Mock {
+    fn load(binding: &OracleBinding, oracle: &AccountInfo) -> Result<OraclePrice> {
+        require_keys_eq!(oracle.key(), binding.oracle, TeraniumError::OracleAccountMismatch);
+        require_keys_eq!(binding.owner, crate::ID, TeraniumError::OracleOwnerMismatch);
+
+        let data = oracle.try_borrow_data()?;
+        let mock = MockPrice::try_deserialize(&mut &data[..]).map_err(|_| TeraniumError::OracleInvalidAccount)?;
+
+        Ok(OraclePrice {
+            price: mock.price,
+            conf: mock.conf,
+            expo: mock.expo,
+            publish_time: mock.publish_time,
+            publish_slot: mock.publish_slot,
+            ema_price: (mock.ema_price != 0).then_some(mock.ema_price),
+        })
+    }
+}
+
+/// Mirror of the Pyth receiver's `PriceUpdateV2` account (after the discriminator),
+/// kept local to avoid depending on the receiver SDK.
+#[derive(AnchorDeserialize)]
+struct PriceUpdateV2 {
+    _write_authority: Pubkey,
+    verification_level: VerificationLevel,
+    price_message: PriceFeedMessage,
+    posted_slot: u64,
+}
+
+#[derive(AnchorDeserialize)]
+enum VerificationLevel {
+    Partial { num_signatures: u8 },
+    Full,
+}
+
+#[derive(AnchorDeserialize)]
+struct PriceFeedMessage {
+    feed_id: [u8; 32],
+    price: i64,
+    conf: u64,
+    exponent: i32,
+    publish_time: i64,
+    _prev_publish_time: i64,
+    ema_price: i64,
+    _ema_conf: u64,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::mem::{offset_of, size_of};
+
+    const FEED_ID: [u8; 32] = [7; 32];
+
+    fn load(binding: &OracleBinding, key: Pubkey, mut data: Vec<u8>) -> Result<OraclePrice> {
+        let owner = Pubkey::new_unique();
+        let mut lamports = 0;
+        let info = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+        load_price(binding, &info)
+    }
+
+    fn load_err(binding: &OracleBinding, key: Pubkey, data: Vec<u8>) -> Error {
+        load(binding, key, data).err().expect("load should fail")
+    }
+
+    fn pull_binding(min_verification_signatures: u8) -> OracleBinding {
+        OracleBinding {
+            kind: OracleKind::PythPull as u8,
+            oracle: Pubkey::default(),
+            owner: Pubkey::new_unique(),
+            feed_id: FEED_ID,
+            min_verification_signatures,
+        }
+    }
+
+    /// `PriceUpdateV2` laid out by hand: discriminator, write authority, verification level,
+    /// price message, posted slot.
+    fn price_update_v2(feed_id: [u8; 32], verification_level: &[u8]) -> Vec<u8> {
+        let mut data = PRICE_UPDATE_V2_DISCRIMINATOR.to_vec();
+        data.extend_from_slice(&[9; 32]);
+        data.extend_from_slice(verification_level);
+        data.extend_from_slice(&feed_id);
+        data.extend_from_slice(&15_025_000_000i64.to_le_bytes()); // price
+        data.extend_from_slice(&4_000_000u64.to_le_bytes()); // conf
+        data.extend_from_slice(&(-8i32).to_le_bytes()); // exponent
+        data.extend_from_slice(&1_700_000_000i64.to_le_bytes()); // publish_time
+        data.extend_from_slice(&1_699_999_999i64.to_le_bytes()); // prev_publish_time
+        data.extend_from_slice(&15_000_000_000i64.to_le_bytes()); // ema_price
+        data.extend_from_slice(&5_000_000u64.to_le_bytes()); // ema_conf
+        data.extend_from_slice(&250_000_000u64.to_le_bytes()); // posted_slot
+        data
+    }
+
+    #[test]
+    fn pyth_pull_decodes_price_update_v2() {
+        let price = load(&pull_binding(0), Pubkey::new_unique(), price_update_v2(FEED_ID, &[1])).unwrap();
+        assert_eq!(price.price, 15_025_000_000);
+        assert_eq!(price.conf, 4_000_000);
+        assert_eq!(price.expo, -8);
+        assert_eq!(price.publish_time, 1_700_000_000);
+        assert_eq!(price.publish_slot, 250_000_000);
+        assert_eq!(price.ema_price, Some(15_000_000_000));
+    }
+
+    #[test]
+    fn pyth_pull_accepts_partial_updates_with_enough_signatures() {
+        let price = load(&pull_binding(3), Pubkey::new_unique(), price_update_v2(FEED_ID, &[0, 3])).unwrap();
+        assert_eq!(price.price, 15_025_000_000);
+        assert_eq!(price.publish_slot, 250_000_000);
+    }
+
+    #[test]
+    fn pyth_pull_rejects_insufficient_verification() {
+        let err = load_err(&pull_binding(3), Pubkey::new_unique(), price_update_v2(FEED_ID, &[0, 2]));
+        assert_eq!(err, error!(TeraniumError::OracleVerificationInsufficient));
+
+        // Partial updates are rejected outright unless the pair opts in with a signature threshold.
+        let err = load_err(&pull_binding(0), Pubkey::new_unique(), price_update_v2(FEED_ID, &[0, 19]));
+        assert_eq!(err, error!(TeraniumError::OracleVerificationInsufficient));
+    }
+
+    #[test]
+    fn pyth_pull_rejects_other_feeds_and_accounts() {
+        let err = load_err(&pull_binding(0), Pubkey::new_unique(), price_update_v2([8; 32], &[1]));
+        assert_eq!(err, error!(TeraniumError::OracleFeedMismatch));
+
+        let mut data = price_update_v2(FEED_ID, &[1]);
+        data[0] ^= 1;
+        let err = load_err(&pull_binding(0), Pubkey::new_unique(), data);
+        assert_eq!(err, error!(TeraniumError::OracleInvalidAccount));
+
+        let mut data = price_update_v2(FEED_ID, &[1]);
+        data.truncate(100);
+        let err = load_err(&pull_binding(0), Pubkey::new_unique(), data);
+        assert_eq!(err, error!(TeraniumError::OracleInvalidAccount));
+    }
+
+    fn address_binding(kind: OracleKind, oracle: Pubkey) -> OracleBinding {
+        OracleBinding {
+            kind: kind as u8,
+            oracle,
+            owner: Pubkey::new_unique(),
+            ..Default::default()
+        }
+    }
+
+    // Mirror of Switchboard On-Demand's zero-copy `PullFeedAccountData` (switchboard-on-demand 0.1.x),
+    // transcribed field by field so fixtures do not reuse the adapter's offsets. Never constructed.
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct OracleSubmission {
+        oracle: Pubkey,
+        slot: u64,
+        landed_at: u64,
+        value: i128,
+    }
+
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct CurrentResult {
+        value: i128,
+        std_dev: i128,
+        mean: i128,
+        range: i128,
+        min_value: i128,
+        max_value: i128,
+        num_samples: u8,
+        submission_idx: u8,
+        padding1: [u8; 6],
+        slot: u64,
+        min_slot: u64,
+        max_slot: u64,
+    }
+
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct CompactResult {
+        std_dev: f32,
+        mean: f32,
+        slot: u64,
+    }
+
+    #[allow(dead_code)]
+    #[repr(C)]
+    struct PullFeedAccountData {
+        submissions: [OracleSubmission; 32],
+        authority: Pubkey,
+        queue: Pubkey,
+        feed_hash: [u8; 32],
+        initialized_at: i64,
+        permissions: u64,
+        max_variance: u64,
+        min_responses: u32,
+        name: [u8; 32],
+        padding1: [u8; 2],
+        historical_result_idx: u8,
+        min_sample_size: u8,
+        last_update_timestamp: i64,
+        lut_slot: u64,
+        reserved1: [u8; 32],
+        result: CurrentResult,
+        max_staleness: u32,
+        padding2: [u8; 12],
+        historical_results: [CompactResult; 32],
+        ebuf4: [u8; 8],
+        ebuf3: [u8; 24],
+        submission_timestamps: [i64; 32],
+    }
+
+    /// Switchboard `PullFeedAccountData` with only the fields the adapter reads populated.
+    fn switchboard_feed(value: i128, std_dev: i128, submission_idx: u8, slot: u64) -> Vec<u8> {
+        fn put(feed: &mut [u8], offset: usize, bytes: &[u8]) {
+            feed[offset..offset + bytes.len()].copy_from_slice(bytes);
+        }
+
+        let mut data = vec![0u8; 8 + size_of::<PullFeedAccountData>()];
+        data[..8].copy_from_slice(&SWITCHBOARD_PULL_FEED_DISCRIMINATOR);
+        let feed = &mut data[8..];
+        put(feed, offset_of!(PullFeedAccountData, result.value), &value.to_le_bytes());
+        put(feed, offset_of!(PullFeedAccountData, result.std_dev), &std_dev.to_le_bytes());
+        put(feed, offset_of!(PullFeedAccountData, result.submission_idx), &[submission_idx]);
+        put(feed, offset_of!(PullFeedAccountData, result.slot), &slot.to_le_bytes());
+        for i in 0..32 {
+            let offset = offset_of!(PullFeedAccountData, submission_timestamps) + i * size_of::<i64>();
+            put(feed, offset, &(1_700_000_000 + i as i64).to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn switchboard_offsets_match_the_pull_feed_layout() {
+        assert_eq!(size_of::<PullFeedAccountData>(), SWITCHBOARD_PULL_FEED_LEN);
+        assert_eq!(offset_of!(PullFeedAccountData, result.value), SWITCHBOARD_RESULT_VALUE_OFFSET);
+        assert_eq!(offset_of!(PullFeedAccountData, result.std_dev), SWITCHBOARD_RESULT_STD_DEV_OFFSET);
+        assert_eq!(
+            offset_of!(PullFeedAccountData, result.submission_idx),
+            SWITCHBOARD_RESULT_SUBMISSION_IDX_OFFSET
+        );
+        assert_eq!(offset_of!(PullFeedAccountData, result.slot), SWITCHBOARD_RESULT_SLOT_OFFSET);
+        assert_eq!(
+            offset_of!(PullFeedAccountData, submission_timestamps),
+            SWITCHBOARD_SUBMISSION_TIMESTAMPS_OFFSET
+        );
+    }
+
+    #[test]
+    fn switchboard_decodes_result_and_submission_timestamp() {
+        let key = Pubkey::new_unique();
+        let binding = address_binding(OracleKind::SwitchboardOnDemand, key);
+        // 150.25 with a 0.04 standard deviation plus one unit of the 18-decimal fixed point.
+        let data = switchboard_feed(150_250_000_000_000_000_000, 40_000_000_000_000_001, 5, 250_000_000);
+
+        let price = load(&binding, key, data).unwrap();
+        assert_eq!(price.price, 15_025_000_000);
+        assert_eq!(price.conf, 4_000_001);
+        assert_eq!(price.expo, -8);
+        assert_eq!(price.publish_time, 1_700_000_005);
+        assert_eq!(price.publish_slot, 250_000_000);
+        assert_eq!(price.ema_price, None);
+    }
+
+    #[test]
+    fn switchboard_rejects_malformed_feeds() {
+        let key = Pubkey::new_unique();
+        let binding = address_binding(OracleKind::SwitchboardOnDemand, key);
+        let value = 150_250_000_000_000_000_000;
+
+        let err = load_err(&binding, key, switchboard_feed(value, 0, 0, 0));
+        assert_eq!(err, error!(TeraniumError::OracleNoPrice));
+
+        let err = load_err(&binding, key, switchboard_feed(value, -1, 0, 1));
+        assert_eq!(err, error!(TeraniumError::OracleInvalidConfidence));
+
+        let err = load_err(&binding, key, switchboard_feed(value, 0, 32, 1));
+        assert_eq!(err, error!(TeraniumError::OracleInvalidAccount));
+
+        let mut data = switchboard_feed(value, 0, 0, 1);
+        data.pop();
+        let err = load_err(&binding, key, data);
+        assert_eq!(err, error!(TeraniumError::OracleInvalidAccount));
+
+        let err = load_err(&binding, Pubkey::new_unique(), switchboard_feed(value, 0, 0, 1));
+        assert_eq!(err, error!(TeraniumError::OracleAccountMismatch));
+    }
+
+    /// Legacy Pyth v2 price account with a populated aggregate, as laid out by the SDK mocks.
+    fn pyth_legacy_account(status: u32) -> Vec<u8> {
+        let mut data = vec![0u8; 3312];
+        let mut put = |offset: usize, bytes: &[u8]| data[offset..offset + bytes.len()].copy_from_slice(bytes);
+        put(0, &0xa1b2c3d4u32.to_le_bytes()); // magic
+        put(4, &2u32.to_le_bytes()); // version
+        put(8, &3u32.to_le_bytes()); // account type: price
+        put(12, &3312u32.to_le_bytes());
+        put(16, &1u32.to_le_bytes()); // price type: price
+        put(20, &(-8i32).to_le_bytes());
+        put(48, &15_000_000_000i64.to_le_bytes()); // ema_price
+        put(72, &5_000_000i64.to_le_bytes()); // ema_conf
+        put(96, &1_700_000_000i64.to_le_bytes()); // timestamp
+        put(176, &249_999_000u64.to_le_bytes()); // prev_slot
+        put(184, &14_900_000_000i64.to_le_bytes()); // prev_price
+        put(192, &3_000_000u64.to_le_bytes()); // prev_conf
+        put(200, &1_699_999_000i64.to_le_bytes()); // prev_timestamp
+        put(208, &15_025_000_000i64.to_le_bytes()); // agg.price
+        put(216, &4_000_000u64.to_le_bytes()); // agg.conf
+        put(224, &status.to_le_bytes()); // agg.status
+        put(232, &250_000_000u64.to_le_bytes()); // agg.pub_slot
+        data
+    }
+
+    #[test]
+    fn pyth_legacy_reads_trading_aggregate() {
+        let key = Pubkey::new_unique();
+        let binding = address_binding(OracleKind::PythLegacy, key);
+
+        let price = load(&binding, key, pyth_legacy_account(PriceStatus::Trading as u32)).unwrap();
+        assert_eq!(price.price, 15_025_000_000);
+        assert_eq!(price.conf, 4_000_000);
+        assert_eq!(price.expo, -8);
+        assert_eq!(price.publish_time, 1_700_000_000);
+        assert_eq!(price.publish_slot, 250_000_000);
+        assert_eq!(price.ema_price, Some(15_000_000_000));
+    }
+
+    #[test]
+    fn pyth_legacy_rejects_non_trading_aggregate() {
+        let key = Pubkey::new_unique();
+        let binding = address_binding(OracleKind::PythLegacy, key);
+
+        // A halted or unknown aggregate must not fall back to the previous trading price.
+        for status in [PriceStatus::Unknown, PriceStatus::Halted, PriceStatus::Auction, PriceStatus::Ignored] {
+            let err = load_err(&binding, key, pyth_legacy_account(status as u32));
+            assert_eq!(err, error!(TeraniumError::OracleNoPrice));
+        }
+
+        let err = load_err(&binding, Pubkey::new_unique(), pyth_legacy_account(PriceStatus::Trading as u32));
+        assert_eq!(err, error!(TeraniumError::OracleAccountMismatch));
+    }
+
+    #[test]
+    fn bind_requires_the_kind_program_as_owner() {
+        let key = Pubkey::new_unique();
+        let data = pyth_legacy_account(PriceStatus::Trading as u32);
+
+        let bind_owned_by = |owner: Pubkey| {
+            let (mut data, mut lamports) = (data.clone(), 0);
+            let info = AccountInfo::new(&key, false, false, &mut lamports, &mut data, &owner, false, 0);
+            bind(OracleKind::PythLegacy as u8, [0; 32], 0, &info)
+        };
+
+        let binding = bind_owned_by(PYTH_LEGACY_PROGRAM_ID).unwrap();
+        assert_eq!(binding.owner, PYTH_LEGACY_PROGRAM_ID);
+        assert_eq!(binding.oracle, key);
+
+        // A copy of the account under any other program is rejected, however well formed.
+        for owner in [Pubkey::new_unique(), PYTH_RECEIVER_PROGRAM_ID, crate::ID] {
+            let err = bind_owned_by(owner).err().expect("bind should fail");
+            assert_eq!(err, error!(TeraniumError::OracleOwnerMismatch));
+        }
+        assert_eq!(program_owner(u8::MAX).unwrap_err(), error!(TeraniumError::InvalidOracleKind));
+
+        // Owners per cluster, spelled out independently of the constants above.
+        #[cfg(not(feature = "devnet"))]
+        let owners = [
+            (OracleKind::PythLegacy, "FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH"),
+            (OracleKind::PythPull, "rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ"),
+            (OracleKind::SwitchboardOnDemand, "SBondMDrcV3K4kxZR1HNVT7osZxAHVHgYXL5Ze1oMUv"),
+        ];
+        #[cfg(feature = "devnet")]
+        let owners = [
+            (OracleKind::PythLegacy, "gSbePebfvPy7tRqimPoVecS2UsBvYv46ynrzWocc92s"),
+            (OracleKind::PythPull, "rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ"),
+            (OracleKind::SwitchboardOnDemand, "Aio4gaXjXzJNVLtzwtNVmSqGKpANtXhybbkhtAC94ji2"),
+        ];
+        for (kind, owner) in owners {
+            assert_eq!(program_owner(kind as u8).unwrap(), owner.parse::<Pubkey>().unwrap());
+        }
+        #[cfg(feature = "localnet")]
+        assert_eq!(program_owner(OracleKind::Mock as u8).unwrap(), crate::ID);
+
+        let feed_key = Pubkey::new_unique();
+        let binding = address_binding(OracleKind::SwitchboardOnDemand, feed_key);
+        let (mut data, mut lamports) = (switchboard_feed(150_250_000_000_000_000_000, 0, 0, 1), 0);
+        let owner = program_owner(binding.kind).unwrap();
+        let info = AccountInfo::new(&feed_key, false, false, &mut lamports, &mut data, &owner, false, 0);
+        assert_eq!(bind(binding.kind, [0; 32], 0, &info).unwrap().owner, owner);
+    }
+
+    fn reading(price: i64, conf: u64, expo: i32, publish_time: i64, ema_price: Option<i64>) -> OraclePrice {
+        OraclePrice {
+            price,
+            conf,
+            expo,
+            publish_time,
+            publish_slot: publish_time as u64,
+            ema_price,
+        }
+    }
+
+    #[test]
+    fn combine_conservative_picks_the_vault_side() {
+        let conservative = DualOraclePricing::Conservative as u8;
+        let primary = reading(10_000, 10, -2, 100, None);
+        let secondary = reading(10_050, 30, -2, 90, Some(9_990));
+
+        // The vault buys base at the lower price and sells it at the higher one, with that feed's confidence.
+        let buy = combine(primary, secondary, 100, conservative, true).unwrap();
+        assert_eq!((buy.price, buy.conf), (10_000, 10));
+        let sell = combine(primary, secondary, 100, conservative, false).unwrap();
+        assert_eq!((sell.price, sell.conf), (10_050, 30));
+
+        // Staleness applies to the older reading; the EMA falls back to the secondary's.
+        assert_eq!((sell.expo, sell.publish_time, sell.publish_slot), (-2, 90, 90));
+        assert_eq!(sell.ema_price, Some(9_990));
+    }
+
+    #[test]
+    fn combine_median_rounds_in_the_vault_favor() {
+        let median = DualOraclePricing::Median as u8;
+        let primary = reading(10_000, 10, -2, 100, Some(10_010));
+        let secondary = reading(10_051, 30, -2, 100, Some(9_990));
+
+        let buy = combine(primary, secondary, 100, median, true).unwrap();
+        assert_eq!((buy.price, buy.conf), (10_025, 30));
+        let sell = combine(primary, secondary, 100, median, false).unwrap();
+        assert_eq!((sell.price, sell.conf), (10_026, 30));
+        assert_eq!(sell.ema_price, Some(10_010));
+    }
+
+    #[test]
+    fn combine_rejects_diverging_or_invalid_feeds() {
+        let conservative = DualOraclePricing::Conservative as u8;
+        let primary = reading(10_000, 10, -2, 100, None);
+
+        // 1% apart: accepted at a 100 bps bound, rejected one bp below it.
+        let secondary = reading(10_100, 10, -2, 100, None);
+        combine(primary, secondary, 100, conservative, true).unwrap();
+        let err = combine(primary, secondary, 99, conservative, true).err().unwrap();
+        assert_eq!(err, error!(TeraniumError::OracleDeviation));
+        let below = reading(9_900, 10, -2, 100, None);
+        let err = combine(primary, below, 99, conservative, false).err().unwrap();
+        assert_eq!(err, error!(TeraniumError::OracleDeviation));
+
+        let err = combine(primary, reading(0, 0, -2, 100, None), 10_000, conservative, true).err().unwrap();
+        assert_eq!(err, error!(TeraniumError::OracleInvalidPrice));
+        let err = combine(reading(-1, 0, -2, 100, None), primary, 10_000, conservative, true).err().unwrap();
+        assert_eq!(err, error!(TeraniumError::OracleInvalidPrice));
+    }
+
+    #[test]
+    fn combine_rescales_the_secondary_to_the_primary_exponent() {
+        let conservative = DualOraclePricing::Conservative as u8;
+        let primary = reading(10_000, 10, -2, 100, None);
+        // 100.005 at expo -3 sits between two primary units; the rounding favors the vault on each side.
+        let secondary = reading(100_005, 1, -3, 100, Some(99_999));
+
+        let sell = combine(primary, secondary, 100, conservative, false).unwrap();
+        assert_eq!((sell.price, sell.conf, sell.expo), (10_001, 1, -2));
+        assert_eq!(sell.ema_price, Some(9_999));
+        let buy = combine(primary, reading(99_995, 1, -3, 100, None), 100, conservative, true).unwrap();
+        assert_eq!((buy.price, buy.conf), (9_999, 1));
+
+        // A coarser secondary exponent scales up exactly.
+        let coarse = reading(101, 1, 0, 100, None);
+        let sell = combine(primary, coarse, 100, conservative, false).unwrap();
+        assert_eq!((sell.price, sell.conf, sell.expo), (10_100, 100, -2));
+
+        // Exponents too far apart overflow instead of wrapping.
+        let err = combine(primary, reading(1, 0, 60, 100, None), 10_000, conservative, true).err().unwrap();
+        assert_eq!(err, error!(TeraniumError::MathOverflow));
+    }
+}
@@ -2,5 +2,17 @@ import { PublicKey } from "@solana/web3.js";
 
 export const TERANIUM_PROGRAM_ID = new PublicKey("Dx9ZBP9kFYjvZX6sY6bHKgyD3BQtTmnhU6apDpMUAMWV");
 
-// Mainnet USDC mint
+// Mainnet USDC mint (default quote mint)
 export const USDC_MINT = new PublicKey("EPjFWdd5AufqSSqeM2qN1xzybapC8G4wEGGkZwyTDt1v");
+
+// Pyth pull-oracle receiver program (owner of `PriceUpdateV2` accounts)
+export const PYTH_RECEIVER_PROGRAM_ID = new PublicKey("rec5EKMGg6MxZYaMdyBfgwp4d5rB9T1VQH5pJv5LtFJ");
+
+// Pyth legacy oracle program (owner of push price accounts); programs built with `--features devnet` expect the
+// devnet deployment
+export const PYTH_LEGACY_PROGRAM_ID = new PublicKey("FsJ3A3u2vn5cTVofAjvy6y5kwABJAqYWpe4975bi2epH");
+export const PYTH_LEGACY_DEVNET_PROGRAM_ID = new PublicKey("gSbePebfvPy7tRqimPoVecS2UsBvYv46ynrzWocc92s");
+
+// Switchboard On-Demand program (owner of pull feed accounts); likewise per cluster
+export const SWITCHBOARD_ON_DEMAND_PROGRAM_ID = new PublicKey("SBondMDrcV3K4kxZR1HNVT7osZxAHVHgYXL5Ze1oMUv");
+export const SWITCHBOARD_ON_DEMAND_DEVNET_PROGRAM_ID = new PublicKey("Aio4gaXjXzJNVLtzwtNVmSqGKpANtXhybbkhtAC94ji2");
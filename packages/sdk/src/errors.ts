@@ -0,0 +1,26 @@
+import { AnchorError } from "@coral-xyz/anchor";
+
+import { TERANIUM_IDL } from "./idl";
+
+const DEPOSIT_CAP_EXCEEDED = TERANIUM_IDL.errors?.find((e) => e.name === "DepositCapExceeded")?.code;
+
+export type DepositCapExceeded = {
+  /** Amount the rejected deposit asked for. */
+  amount: bigint;
+  /** Largest deposit the vault-wide and per-user caps still allow. */
+  headroom: bigint;
+};
+
+/**
+ * Extracts the amount and remaining cap headroom from a failed deposit.
+ * Accepts an AnchorError or any error carrying transaction logs; returns null for other errors.
+ */
+export function parseDepositCapExceeded(err: unknown): DepositCapExceeded | null {
+  const logs = (err as { logs?: unknown } | null)?.logs;
+  const anchorError = err instanceof AnchorError ? err : Array.isArray(logs) ? AnchorError.parse(logs) : null;
+  if (!anchorError || anchorError.error.errorCode.number !== DEPOSIT_CAP_EXCEEDED) return null;
+
+  const values = anchorError.error.comparedValues;
+  if (!values || typeof values[0] !== "string" || typeof values[1] !== "string") return null;
+  return { amount: BigInt(values[0]), headroom: BigInt(values[1]) };
+}
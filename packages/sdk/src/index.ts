@@ -1,4 +1,14 @@
 export { Teranium } from "./teranium";
 export * as pdas from "./pdas";
+export * as mocks from "./mocks";
+export { parseDepositCapExceeded, type DepositCapExceeded } from "./errors";
 export { TERANIUM_IDL } from "./idl";
-export { TERANIUM_PROGRAM_ID, USDC_MINT } from "./constants";
+export {
+  PYTH_LEGACY_DEVNET_PROGRAM_ID,
+  PYTH_LEGACY_PROGRAM_ID,
+  PYTH_RECEIVER_PROGRAM_ID,
+  SWITCHBOARD_ON_DEMAND_DEVNET_PROGRAM_ID,
+  SWITCHBOARD_ON_DEMAND_PROGRAM_ID,
+  TERANIUM_PROGRAM_ID,
+  USDC_MINT,
+} from "./constants";
@@ -0,0 +1,134 @@
+import { PublicKey } from "@solana/web3.js";
+
+// Account data builders for local tests (e.g. `solana-test-validator --account` or bankrun).
+// They produce the minimum layout the program reads; everything else is zeroed.
+
+export const PYTH_LEGACY_PRICE_ACCOUNT_SIZE = 3312;
+
+const PYTH_MAGIC = 0xa1b2c3d4;
+const PYTH_VERSION_2 = 2;
+const PYTH_ACCOUNT_TYPE_PRICE = 3;
+const PYTH_PRICE_TYPE_PRICE = 1;
+const PYTH_STATUS_TRADING = 1;
+
+// sha256("account:PriceUpdateV2")[..8]
+const PRICE_UPDATE_V2_DISCRIMINATOR = Buffer.from([34, 241, 35, 99, 157, 126, 244, 205]);
+
+// Switchboard On-Demand `PullFeedAccountData`
+const SWITCHBOARD_PULL_FEED_DISCRIMINATOR = Buffer.from([196, 27, 108, 196, 10, 215, 219, 40]);
+export const SWITCHBOARD_PULL_FEED_ACCOUNT_SIZE = 8 + 3200;
+
+// sha256("account:MockPrice")[..8]
+const MOCK_PRICE_DISCRIMINATOR = Buffer.from([2, 6, 235, 132, 88, 250, 211, 139]);
+
+export type MockPriceParams = {
+  price: bigint;
+  conf: bigint;
+  expo: number;
+  publishTime: bigint;
+  emaPrice?: bigint;
+  emaConf?: bigint;
+};
+
+/** Legacy Pyth push price account (`SolanaPriceAccount`) with an aggregate in `Trading` status. */
+export function encodeLegacyPriceAccount(params: MockPriceParams & { slot?: bigint }): Buffer {
+  const data = Buffer.alloc(PYTH_LEGACY_PRICE_ACCOUNT_SIZE);
+  const slot = params.slot ?? 0n;
+
+  data.writeUInt32LE(PYTH_MAGIC, 0);
+  data.writeUInt32LE(PYTH_VERSION_2, 4);
+  data.writeUInt32LE(PYTH_ACCOUNT_TYPE_PRICE, 8);
+  data.writeUInt32LE(PYTH_LEGACY_PRICE_ACCOUNT_SIZE, 12);
+  data.writeUInt32LE(PYTH_PRICE_TYPE_PRICE, 16);
+  data.writeInt32LE(params.expo, 20);
+  data.writeBigUInt64LE(slot, 32); // last_slot
+  data.writeBigUInt64LE(slot, 40); // valid_slot
+  data.writeBigInt64LE(params.emaPrice ?? params.price, 48); // ema_price.val
+  data.writeBigInt64LE(params.emaConf ?? params.conf, 72); // ema_conf.val
+  data.writeBigInt64LE(params.publishTime, 96); // timestamp
+  data.writeBigUInt64LE(slot, 176); // prev_slot
+  data.writeBigInt64LE(params.price, 184); // prev_price
+  data.writeBigUInt64LE(params.conf, 192); // prev_conf
+  data.writeBigInt64LE(params.publishTime, 200); // prev_timestamp
+  data.writeBigInt64LE(params.price, 208); // agg.price
+  data.writeBigUInt64LE(params.conf, 216); // agg.conf
+  data.writeUInt32LE(PYTH_STATUS_TRADING, 224); // agg.status
+  data.writeBigUInt64LE(slot, 232); // agg.pub_slot
+  return data;
+}
+
+export type MockVerificationLevel = { partial: { numSignatures: number } } | { full: Record<string, never> };
+
+/** Pyth receiver `PriceUpdateV2` account (pull model). Owner must be set to the pair's oracle owner. */
+export function encodePriceUpdateV2(
+  params: MockPriceParams & {
+    feedId: Uint8Array;
+    verificationLevel?: MockVerificationLevel;
+    writeAuthority?: PublicKey;
+    prevPublishTime?: bigint;
+    postedSlot?: bigint;
+  },
+): Buffer {
+  if (params.feedId.length !== 32) throw new Error("feedId must be 32 bytes");
+  const level = params.verificationLevel ?? { full: {} };
+  const levelBytes =
+    "partial" in level ? Buffer.from([0, level.partial.numSignatures]) : Buffer.from([1]);
+
+  const message = Buffer.alloc(32 + 8 + 8 + 4 + 8 + 8 + 8 + 8);
+  Buffer.from(params.feedId).copy(message, 0);
+  message.writeBigInt64LE(params.price, 32);
+  message.writeBigUInt64LE(params.conf, 40);
+  message.writeInt32LE(params.expo, 48);
+  message.writeBigInt64LE(params.publishTime, 52);
+  message.writeBigInt64LE(params.prevPublishTime ?? params.publishTime, 60);
+  message.writeBigInt64LE(params.emaPrice ?? params.price, 68);
+  message.writeBigUInt64LE(params.emaConf ?? params.conf, 76);
+
+  const postedSlot = Buffer.alloc(8);
+  postedSlot.writeBigUInt64LE(params.postedSlot ?? 0n, 0);
+
+  return Buffer.concat([
+    PRICE_UPDATE_V2_DISCRIMINATOR,
+    (params.writeAuthority ?? PublicKey.default).toBuffer(),
+    levelBytes,
+    message,
+    postedSlot,
+  ]);
+}
+
+/**
+ * Switchboard On-Demand pull feed with a single current result. `value` and `stdDev` are
+ * fixed point with 18 decimals, as stored on chain.
+ */
+export function encodeSwitchboardPullFeed(params: {
+  value: bigint;
+  stdDev: bigint;
+  slot: bigint;
+  timestamp: bigint;
+}): Buffer {
+  const data = Buffer.alloc(SWITCHBOARD_PULL_FEED_ACCOUNT_SIZE);
+  SWITCHBOARD_PULL_FEED_DISCRIMINATOR.copy(data, 0);
+  const result = 8 + 2256;
+  data.writeBigInt64LE(BigInt.asIntN(64, params.value), result); // value (i128, low)
+  data.writeBigInt64LE(params.value >> 64n, result + 8); // value (i128, high)
+  data.writeBigInt64LE(BigInt.asIntN(64, params.stdDev), result + 16); // std_dev (i128, low)
+  data.writeBigInt64LE(params.stdDev >> 64n, result + 24); // std_dev (i128, high)
+  data.writeUInt8(1, result + 96); // num_samples
+  data.writeUInt8(0, result + 97); // submission_idx
+  data.writeBigUInt64LE(params.slot, result + 104); // slot
+  data.writeBigInt64LE(params.timestamp, 8 + 2944); // submission_timestamps[0]
+  return data;
+}
+
+/** `MockPrice` account for `localnet` program builds. Owner must be the Teranium program. */
+export function encodeMockPrice(params: Omit<MockPriceParams, "emaConf"> & { publishSlot?: bigint }): Buffer {
+  const data = Buffer.alloc(8 + 8 + 8 + 4 + 8 + 8 + 8);
+  MOCK_PRICE_DISCRIMINATOR.copy(data, 0);
+  data.writeBigInt64LE(params.price, 8);
+  data.writeBigUInt64LE(params.conf, 16);
+  data.writeInt32LE(params.expo, 24);
+  data.writeBigInt64LE(params.publishTime, 28);
+  data.writeBigUInt64LE(params.publishSlot ?? 0n, 36);
+  data.writeBigInt64LE(params.emaPrice ?? 0n, 44); // 0 = no EMA
+  return data;
+}
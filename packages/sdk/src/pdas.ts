@@ -1,5 +1,9 @@
 import { PublicKey } from "@solana/web3.js";
 
+export function findProtocolConfigPda(programId: PublicKey): [PublicKey, number] {
+  return PublicKey.findProgramAddressSync([Buffer.from("protocol_config")], programId);
+}
+
 export function findVaultPda(programId: PublicKey, mint: PublicKey): [PublicKey, number] {
   return PublicKey.findProgramAddressSync([Buffer.from("vault"), mint.toBuffer()], programId);
 }
@@ -8,9 +12,30 @@ export function findVaultAuthorityPda(programId: PublicKey, vault: PublicKey): [
   return PublicKey.findProgramAddressSync([Buffer.from("vault_authority"), vault.toBuffer()], programId);
 }
 
+export function findPairConfigPda(programId: PublicKey, baseVault: PublicKey, quoteVault: PublicKey): [PublicKey, number] {
+  return PublicKey.findProgramAddressSync(
+    [Buffer.from("pair_config"), baseVault.toBuffer(), quoteVault.toBuffer()],
+    programId,
+  );
+}
+
 export function findUserPositionPda(programId: PublicKey, vault: PublicKey, owner: PublicKey): [PublicKey, number] {
   return PublicKey.findProgramAddressSync(
     [Buffer.from("user_position"), vault.toBuffer(), owner.toBuffer()],
     programId,
   );
 }
+
+export function findPendingChangePda(programId: PublicKey, id: bigint): [PublicKey, number] {
+  const seed = Buffer.alloc(8);
+  seed.writeBigUInt64LE(id, 0);
+  return PublicKey.findProgramAddressSync([Buffer.from("pending_change"), seed], programId);
+}
+
+export function findWrapTokenAccountPda(programId: PublicKey, owner: PublicKey): [PublicKey, number] {
+  return PublicKey.findProgramAddressSync([Buffer.from("wrap"), owner.toBuffer()], programId);
+}
+
+export function findUnwrapTokenAccountPda(programId: PublicKey, vault: PublicKey, owner: PublicKey): [PublicKey, number] {
+  return PublicKey.findProgramAddressSync([Buffer.from("unwrap"), vault.toBuffer(), owner.toBuffer()], programId);
+}
@@ -5,11 +5,14 @@ import {
 } from "@coral-xyz/anchor";
 import {
   ASSOCIATED_TOKEN_PROGRAM_ID,
+  NATIVE_MINT,
+  TOKEN_2022_PROGRAM_ID,
   TOKEN_PROGRAM_ID,
   getAssociatedTokenAddressSync,
 } from "@solana/spl-token";
 import {
   PublicKey,
+  type AccountMeta,
   type Transaction,
   type VersionedTransaction,
   SystemProgram,
@@ -20,7 +23,15 @@ import {
 
 import { TERANIUM_IDL } from "./idl";
 import { TERANIUM_PROGRAM_ID, USDC_MINT } from "./constants";
-import { findUserPositionPda, findVaultAuthorityPda, findVaultPda } from "./pdas";
+import {
+  findPairConfigPda,
+  findProtocolConfigPda,
+  findUnwrapTokenAccountPda,
+  findUserPositionPda,
+  findVaultAuthorityPda,
+  findVaultPda,
+  findWrapTokenAccountPda,
+} from "./pdas";
 
 export type Commitment = "processed" | "confirmed" | "finalized";
 
@@ -39,30 +50,95 @@ export type TeraniumConfig = {
 
 export type InitializeVaultParams = {
   mint: PublicKey;
+  /** SPL Token or Token-2022; read from the mint account when omitted. */
+  tokenProgram?: PublicKey;
 };
 
 export type DepositParams = {
   mint: PublicKey;
   amount: bigint;
   userTokenAccount?: PublicKey;
+  tokenProgram?: PublicKey;
+  /** Extra accounts for a Token-2022 transfer hook (hook program, validation account and its extra metas). */
+  transferHookAccounts?: AccountMeta[];
 };
 
 export type WithdrawParams = {
   mint: PublicKey;
   amount: bigint;
   userTokenAccount?: PublicKey;
+  tokenProgram?: PublicKey;
+  /** Extra accounts for a Token-2022 transfer hook (hook program, validation account and its extra metas). */
+  transferHookAccounts?: AccountMeta[];
+};
+
+export type ClosePositionParams = {
+  mint: PublicKey;
+};
+
+/** Native SOL deposit/withdraw in lamports; the vault is the wrapped SOL vault. */
+export type SolAmountParams = {
+  amount: bigint;
 };
 
-export type OracleSwapDirection = "baseToUsdc" | "usdcToBase";
+/** `baseToUsdc` / `usdcToBase` are accepted as aliases from before quote mints were configurable. */
+export type OracleSwapDirection = "baseToQuote" | "quoteToBase" | "baseToUsdc" | "usdcToBase";
 
-export type OracleSwapParams = {
+type OracleSwapAccountParams = {
   baseMint: PublicKey;
+  /** Quote mint of the pair; defaults to mainnet USDC. */
+  quoteMint?: PublicKey;
   direction: OracleSwapDirection;
-  amount: bigint;
-  maxSlippageBps: number;
   pythPriceAccount: PublicKey;
+  /** Required when the pair has a secondary oracle. */
+  secondaryPriceAccount?: PublicKey;
   userBaseTokenAccount?: PublicKey;
-  userUsdcTokenAccount?: PublicKey;
+  userQuoteTokenAccount?: PublicKey;
+  /** Token programs of the two mints; read from the mint accounts when omitted. */
+  baseTokenProgram?: PublicKey;
+  quoteTokenProgram?: PublicKey;
+  /** Extra accounts for Token-2022 transfer hooks on either leg. */
+  transferHookAccounts?: AccountMeta[];
+};
+
+export type TripCircuitBreakerParams = {
+  baseMint: PublicKey;
+  /** Quote mint of the pair; defaults to mainnet USDC. */
+  quoteMint?: PublicKey;
+  pythPriceAccount: PublicKey;
+  /** Required when the pair has a secondary oracle. */
+  secondaryPriceAccount?: PublicKey;
+  /** Trip unconditionally as the admin or guardian (the provider wallet) instead of checking for a price jump. */
+  manual?: boolean;
+};
+
+/** Last unix timestamp or slot at which the swap may execute (inclusive). */
+export type SwapDeadline = { unixTimestamp: bigint } | { slot: bigint };
+
+export type OracleSwapParams = OracleSwapAccountParams & {
+  amount: bigint;
+  maxSlippageBps: number;
+  /** Minimum amount the user must receive after fees; uses `oracle_swap_v2` when set. */
+  minAmountOut?: bigint;
+  /** Uses `oracle_swap_v2` when set. */
+  deadline?: SwapDeadline;
+};
+
+/** Swap with native SOL on one side (either mint of the pair must be the native mint). */
+export type OracleSwapSolParams = OracleSwapAccountParams & {
+  /** Lamports when selling SOL, otherwise quote/base token units. */
+  amount: bigint;
+  maxSlippageBps: number;
+  minAmountOut?: bigint;
+  deadline?: SwapDeadline;
+};
+
+export type OracleSwapExactOutParams = OracleSwapAccountParams & {
+  /** Exact amount the user receives after fees. */
+  amountOut: bigint;
+  maxAmountIn: bigint;
+  maxSlippageBps: number;
+  deadline?: SwapDeadline;
 };
 
 function toU64(amount: bigint): bigint {
@@ -72,6 +148,19 @@ function toU64(amount: bigint): bigint {
   return amount;
 }
 
+function toU64Min(amount: bigint): bigint {
+  if (amount < 0n) throw new Error("minAmountOut must be >= 0");
+  const max = (1n << 64n) - 1n;
+  if (amount > max) throw new Error("minAmountOut exceeds u64");
+  return amount;
+}
+
+function toSwapDeadline(deadline: SwapDeadline | undefined) {
+  if (deadline === undefined) return null;
+  if ("unixTimestamp" in deadline) return { unixTimestamp: { unixTimestamp: deadline.unixTimestamp } };
+  return { slot: { slot: deadline.slot } };
+}
+
 function toU16(value: number): number {
   if (!Number.isInteger(value)) throw new Error("maxSlippageBps must be integer");
   if (value < 0 || value > 10_000) throw new Error("maxSlippageBps out of range");
@@ -87,10 +176,17 @@ export class Teranium {
     initializeVault: (params: InitializeVaultParams) => Promise<TransactionSignature>;
     deposit: (params: DepositParams) => Promise<TransactionSignature>;
     withdraw: (params: WithdrawParams) => Promise<TransactionSignature>;
+    depositSol: (params: SolAmountParams) => Promise<TransactionSignature>;
+    withdrawSol: (params: SolAmountParams) => Promise<TransactionSignature>;
+    closePosition: (params: ClosePositionParams) => Promise<TransactionSignature>;
   };
 
   readonly swap: {
     execute: (params: OracleSwapParams) => Promise<TransactionSignature>;
+    executeExactOut: (params: OracleSwapExactOutParams) => Promise<TransactionSignature>;
+    executeSol: (params: OracleSwapSolParams) => Promise<TransactionSignature>;
+    /** Pauses a pair's swaps on a price jump, or unconditionally with `manual` (admin or guardian). */
+    tripCircuitBreaker: (params: TripCircuitBreakerParams) => Promise<TransactionSignature>;
   };
 
   constructor(cfg: TeraniumConfig) {
@@ -108,10 +204,11 @@ export class Teranium {
     this.program = new Program(idlWithAddress, this.provider);
 
     this.vault = {
-      initializeVault: async ({ mint }) => {
+      initializeVault: async ({ mint, tokenProgram }) => {
+        const program = tokenProgram ?? (await this.mintTokenProgram(mint));
         const [vault] = findVaultPda(this.programId, mint);
         const [vaultAuthority] = findVaultAuthorityPda(this.programId, vault);
-        const vaultTokenAccount = getAssociatedTokenAddressSync(mint, vaultAuthority, true);
+        const vaultTokenAccount = getAssociatedTokenAddressSync(mint, vaultAuthority, true, program);
 
         return await this.program.methods
           .initializeVault(mint)
@@ -122,113 +219,333 @@ export class Teranium {
             vaultAuthority,
             vaultTokenAccount,
             systemProgram: SystemProgram.programId,
-            tokenProgram: TOKEN_PROGRAM_ID,
+            tokenProgram: program,
             associatedTokenProgram: ASSOCIATED_TOKEN_PROGRAM_ID,
             rent: SYSVAR_RENT_PUBKEY,
           })
           .rpc();
       },
 
-      deposit: async ({ mint, amount, userTokenAccount }) => {
+      deposit: async ({ mint, amount, userTokenAccount, tokenProgram, transferHookAccounts }) => {
         const u64 = toU64(amount);
         const owner = this.provider.wallet.publicKey;
+        const program = tokenProgram ?? (await this.mintTokenProgram(mint));
 
+        const [protocolConfig] = findProtocolConfigPda(this.programId);
         const [vault] = findVaultPda(this.programId, mint);
         const [vaultAuthority] = findVaultAuthorityPda(this.programId, vault);
         const [userPosition] = findUserPositionPda(this.programId, vault, owner);
 
-        const userAta = userTokenAccount ?? getAssociatedTokenAddressSync(mint, owner, false);
-        const vaultAta = getAssociatedTokenAddressSync(mint, vaultAuthority, true);
+        const userAta = userTokenAccount ?? getAssociatedTokenAddressSync(mint, owner, false, program);
+        const vaultAta = getAssociatedTokenAddressSync(mint, vaultAuthority, true, program);
 
         return await this.program.methods
           .deposit(u64)
           .accounts({
             owner,
+            protocolConfig,
             vault,
             vaultAuthority,
+            mint,
             userPosition,
             userTokenAccount: userAta,
             vaultTokenAccount: vaultAta,
             systemProgram: SystemProgram.programId,
-            tokenProgram: TOKEN_PROGRAM_ID,
+            tokenProgram: program,
             rent: SYSVAR_RENT_PUBKEY,
           })
+          .remainingAccounts(transferHookAccounts ?? [])
           .rpc();
       },
 
-      withdraw: async ({ mint, amount, userTokenAccount }) => {
+      withdraw: async ({ mint, amount, userTokenAccount, tokenProgram, transferHookAccounts }) => {
         const u64 = toU64(amount);
         const owner = this.provider.wallet.publicKey;
+        const program = tokenProgram ?? (await this.mintTokenProgram(mint));
 
+        const [protocolConfig] = findProtocolConfigPda(this.programId);
         const [vault] = findVaultPda(this.programId, mint);
         const [vaultAuthority] = findVaultAuthorityPda(this.programId, vault);
         const [userPosition] = findUserPositionPda(this.programId, vault, owner);
 
-        const userAta = userTokenAccount ?? getAssociatedTokenAddressSync(mint, owner, false);
-        const vaultAta = getAssociatedTokenAddressSync(mint, vaultAuthority, true);
+        const userAta = userTokenAccount ?? getAssociatedTokenAddressSync(mint, owner, false, program);
+        const vaultAta = getAssociatedTokenAddressSync(mint, vaultAuthority, true, program);
 
         return await this.program.methods
           .withdraw(u64)
           .accounts({
             owner,
+            protocolConfig,
             vault,
             vaultAuthority,
+            mint,
             userPosition,
             userTokenAccount: userAta,
             vaultTokenAccount: vaultAta,
+            tokenProgram: program,
+          })
+          .remainingAccounts(transferHookAccounts ?? [])
+          .rpc();
+      },
+
+      depositSol: async ({ amount }) => {
+        const u64 = toU64(amount);
+        const owner = this.provider.wallet.publicKey;
+
+        const [protocolConfig] = findProtocolConfigPda(this.programId);
+        const [vault] = findVaultPda(this.programId, NATIVE_MINT);
+        const [vaultAuthority] = findVaultAuthorityPda(this.programId, vault);
+        const [userPosition] = findUserPositionPda(this.programId, vault, owner);
+
+        return await this.program.methods
+          .depositSol(u64)
+          .accounts({
+            owner,
+            protocolConfig,
+            vault,
+            vaultAuthority,
+            userPosition,
+            vaultTokenAccount: getAssociatedTokenAddressSync(NATIVE_MINT, vaultAuthority, true),
+            systemProgram: SystemProgram.programId,
             tokenProgram: TOKEN_PROGRAM_ID,
           })
           .rpc();
       },
+
+      withdrawSol: async ({ amount }) => {
+        const u64 = toU64(amount);
+        const owner = this.provider.wallet.publicKey;
+
+        const [protocolConfig] = findProtocolConfigPda(this.programId);
+        const [vault] = findVaultPda(this.programId, NATIVE_MINT);
+        const [vaultAuthority] = findVaultAuthorityPda(this.programId, vault);
+        const [userPosition] = findUserPositionPda(this.programId, vault, owner);
+        const [unwrapTokenAccount] = findUnwrapTokenAccountPda(this.programId, vault, owner);
+
+        return await this.program.methods
+          .withdrawSol(u64)
+          .accounts({
+            owner,
+            protocolConfig,
+            vault,
+            vaultAuthority,
+            mint: NATIVE_MINT,
+            userPosition,
+            vaultTokenAccount: getAssociatedTokenAddressSync(NATIVE_MINT, vaultAuthority, true),
+            unwrapTokenAccount,
+            systemProgram: SystemProgram.programId,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .rpc();
+      },
+
+      closePosition: async ({ mint }) => {
+        const owner = this.provider.wallet.publicKey;
+        const [vault] = findVaultPda(this.programId, mint);
+        const [userPosition] = findUserPositionPda(this.programId, vault, owner);
+
+        // The vault is only needed to forfeit dust shares; a closed vault is passed as absent.
+        const vaultInfo = await this.provider.connection.getAccountInfo(vault);
+
+        return await this.program.methods
+          .closePosition()
+          .accounts({ owner, userPosition, vault: vaultInfo ? vault : null })
+          .rpc();
+      },
     };
 
     this.swap = {
       execute: async ({
         baseMint,
+        quoteMint,
         direction,
         amount,
         maxSlippageBps,
+        minAmountOut,
+        deadline,
         pythPriceAccount,
+        secondaryPriceAccount,
         userBaseTokenAccount,
-        userUsdcTokenAccount,
+        userQuoteTokenAccount,
+        baseTokenProgram,
+        quoteTokenProgram,
+        transferHookAccounts,
       }) => {
         const u64 = toU64(amount);
         const u16 = toU16(maxSlippageBps);
-        const user = this.provider.wallet.publicKey;
 
-        const [baseVault] = findVaultPda(this.programId, baseMint);
-        const [baseVaultAuthority] = findVaultAuthorityPda(this.programId, baseVault);
-        const baseVaultTokenAccount = getAssociatedTokenAddressSync(baseMint, baseVaultAuthority, true);
+        const method =
+          minAmountOut === undefined && deadline === undefined
+            ? this.program.methods.oracleSwap(u64, u16)
+            : this.program.methods.oracleSwapV2(u64, u16, toU64Min(minAmountOut ?? 0n), toSwapDeadline(deadline));
+
+        return await method
+          .accounts(
+            await this.oracleSwapAccounts({
+              baseMint,
+              quoteMint,
+              direction,
+              pythPriceAccount,
+              secondaryPriceAccount,
+              userBaseTokenAccount,
+              userQuoteTokenAccount,
+              baseTokenProgram,
+              quoteTokenProgram,
+            }),
+          )
+          .remainingAccounts(transferHookAccounts ?? [])
+          .rpc();
+      },
 
-        const [usdcVault] = findVaultPda(this.programId, USDC_MINT);
-        const [usdcVaultAuthority] = findVaultAuthorityPda(this.programId, usdcVault);
-        const usdcVaultTokenAccount = getAssociatedTokenAddressSync(USDC_MINT, usdcVaultAuthority, true);
+      executeExactOut: async ({
+        baseMint,
+        quoteMint,
+        direction,
+        amountOut,
+        maxAmountIn,
+        maxSlippageBps,
+        deadline,
+        pythPriceAccount,
+        secondaryPriceAccount,
+        userBaseTokenAccount,
+        userQuoteTokenAccount,
+        baseTokenProgram,
+        quoteTokenProgram,
+        transferHookAccounts,
+      }) => {
+        return await this.program.methods
+          .oracleSwapExactOut(toU64(amountOut), toU64(maxAmountIn), toU16(maxSlippageBps), toSwapDeadline(deadline))
+          .accounts(
+            await this.oracleSwapAccounts({
+              baseMint,
+              quoteMint,
+              direction,
+              pythPriceAccount,
+              secondaryPriceAccount,
+              userBaseTokenAccount,
+              userQuoteTokenAccount,
+              baseTokenProgram,
+              quoteTokenProgram,
+            }),
+          )
+          .remainingAccounts(transferHookAccounts ?? [])
+          .rpc();
+      },
 
-        const userBaseAta = userBaseTokenAccount ?? getAssociatedTokenAddressSync(baseMint, user, false);
-        const userUsdcAta = userUsdcTokenAccount ?? getAssociatedTokenAddressSync(USDC_MINT, user, false);
+      executeSol: async ({ amount, maxSlippageBps, minAmountOut, deadline, transferHookAccounts, ...accountParams }) => {
+        const user = this.provider.wallet.publicKey;
+        const accounts = await this.oracleSwapAccounts(accountParams);
+        const solIsBase = accounts.baseMint.equals(NATIVE_MINT);
+        if (!solIsBase && !accounts.quoteMint.equals(NATIVE_MINT)) throw new Error("pair has no native SOL side");
+        const baseToQuote = accountParams.direction === "baseToQuote" || accountParams.direction === "baseToUsdc";
+        const sellingSol = solIsBase === baseToQuote;
+
+        // The native side goes through a temporary wrap account the program opens and closes; no wSOL ATA is used.
+        const [wrapTokenAccount] = findWrapTokenAccountPda(this.programId, user);
+        const swap = {
+          ...accounts,
+          userFromTokenAccount: sellingSol ? null : accounts.userFromTokenAccount,
+          userToTokenAccount: sellingSol ? accounts.userToTokenAccount : null,
+        };
 
-        const userFromTokenAccount = direction === "baseToUsdc" ? userBaseAta : userUsdcAta;
-        const userToTokenAccount = direction === "baseToUsdc" ? userUsdcAta : userBaseAta;
+        return await this.program.methods
+          .oracleSwapSol(toU64(amount), toU16(maxSlippageBps), toU64Min(minAmountOut ?? 0n), toSwapDeadline(deadline))
+          .accounts({
+            swap,
+            nativeMint: NATIVE_MINT,
+            wrapTokenAccount,
+            systemProgram: SystemProgram.programId,
+            tokenProgram: TOKEN_PROGRAM_ID,
+          })
+          .remainingAccounts(transferHookAccounts ?? [])
+          .rpc();
+      },
+
+      tripCircuitBreaker: async ({
+        baseMint,
+        quoteMint = USDC_MINT,
+        pythPriceAccount,
+        secondaryPriceAccount,
+        manual = false,
+      }) => {
+        const [protocolConfig] = findProtocolConfigPda(this.programId);
+        const [baseVault] = findVaultPda(this.programId, baseMint);
+        const [quoteVault] = findVaultPda(this.programId, quoteMint);
+        const [pairConfig] = findPairConfigPda(this.programId, baseVault, quoteVault);
 
         return await this.program.methods
-          .oracleSwap(u64, u16)
+          .tripCircuitBreaker()
           .accounts({
-            user,
-            baseVault,
-            baseVaultAuthority,
-            baseVaultTokenAccount,
-            baseMint,
-            usdcVault,
-            usdcVaultAuthority,
-            usdcVaultTokenAccount,
-            usdcMint: USDC_MINT,
-            userFromTokenAccount,
-            userToTokenAccount,
+            authority: manual ? this.provider.wallet.publicKey : null,
+            protocolConfig,
+            pairConfig,
             pythPriceAccount,
-            tokenProgram: TOKEN_PROGRAM_ID,
+            secondaryPriceAccount: secondaryPriceAccount ?? null,
           })
           .rpc();
       },
     };
   }
+
+  private async mintTokenProgram(mint: PublicKey): Promise<PublicKey> {
+    const info = await this.provider.connection.getAccountInfo(mint);
+    if (!info) throw new Error(`mint ${mint.toBase58()} not found`);
+    if (!info.owner.equals(TOKEN_PROGRAM_ID) && !info.owner.equals(TOKEN_2022_PROGRAM_ID)) {
+      throw new Error(`mint ${mint.toBase58()} is not owned by a token program`);
+    }
+    return info.owner;
+  }
+
+  private async oracleSwapAccounts({
+    baseMint,
+    quoteMint = USDC_MINT,
+    direction,
+    pythPriceAccount,
+    secondaryPriceAccount,
+    userBaseTokenAccount,
+    userQuoteTokenAccount,
+    baseTokenProgram,
+    quoteTokenProgram,
+  }: OracleSwapAccountParams) {
+    const user = this.provider.wallet.publicKey;
+    const baseProgram = baseTokenProgram ?? (await this.mintTokenProgram(baseMint));
+    const quoteProgram = quoteTokenProgram ?? (await this.mintTokenProgram(quoteMint));
+
+    const [protocolConfig] = findProtocolConfigPda(this.programId);
+    const [baseVault] = findVaultPda(this.programId, baseMint);
+    const [baseVaultAuthority] = findVaultAuthorityPda(this.programId, baseVault);
+    const baseVaultTokenAccount = getAssociatedTokenAddressSync(baseMint, baseVaultAuthority, true, baseProgram);
+
+    const [quoteVault] = findVaultPda(this.programId, quoteMint);
+    const [quoteVaultAuthority] = findVaultAuthorityPda(this.programId, quoteVault);
+    const quoteVaultTokenAccount = getAssociatedTokenAddressSync(quoteMint, quoteVaultAuthority, true, quoteProgram);
+    const [pairConfig] = findPairConfigPda(this.programId, baseVault, quoteVault);
+
+    const userBaseAta = userBaseTokenAccount ?? getAssociatedTokenAddressSync(baseMint, user, false, baseProgram);
+    const userQuoteAta = userQuoteTokenAccount ?? getAssociatedTokenAddressSync(quoteMint, user, false, quoteProgram);
+
+    const baseToQuote = direction === "baseToQuote" || direction === "baseToUsdc";
+    const userFromTokenAccount = baseToQuote ? userBaseAta : userQuoteAta;
+    const userToTokenAccount = baseToQuote ? userQuoteAta : userBaseAta;
+
+    return {
+      user,
+      protocolConfig,
+      baseVault,
+      baseVaultAuthority,
+      baseVaultTokenAccount,
+      baseMint,
+      quoteVault,
+      quoteVaultAuthority,
+      quoteVaultTokenAccount,
+      quoteMint,
+      userFromTokenAccount,
+      userToTokenAccount,
+      pairConfig,
+      pythPriceAccount,
+      secondaryPriceAccount: secondaryPriceAccount ?? null,
+      baseTokenProgram: baseProgram,
+      quoteTokenProgram: quoteProgram,
+    };
+  }
 }